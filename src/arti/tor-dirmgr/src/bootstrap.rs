@@ -3,14 +3,85 @@
 
 // Code mostly copied from Arti.
 
-use crate::{
-    DirState, Result,
-};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
 
+use async_trait::async_trait;
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use log::{debug, trace, warn};
+use rand::Rng;
+use tokio::time::sleep;
+
+use crate::retry::{DownloadSchedule, RetryTracker};
+use crate::status::{DirProgress, ProgressClock, DEFAULT_STALL_TIMEOUT};
+use crate::{DirBlockage, DirState, DocId, Error, Result};
+
+/// Process-global counter backing [`AttemptId::new`].
+static NEXT_ATTEMPT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Identifies a single call to [`load`], so its state transitions can be told apart in the trace
+/// output from any other `load` call running concurrently or interleaved with it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct AttemptId(u64);
+
+impl AttemptId {
+    /// Mint a new, process-unique attempt id.
+    fn new() -> Self {
+        AttemptId(NEXT_ATTEMPT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl fmt::Display for AttemptId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A source of directory documents over the network.
+///
+/// This is the "how to download" half of bootstrapping, kept separate from [`DirState`] (the
+/// "what to download" half) so that the loop in [`load`] can be driven against a mock client in
+/// tests instead of a real circuit manager.
+#[async_trait]
+pub(crate) trait DirClient: Send + Sync {
+    /// Fetch as many of `ids` as possible and return whatever came back successfully as
+    /// `(id, text)` pairs.
+    ///
+    /// A client may return fewer entries than it was asked for if some of the requests failed;
+    /// anything missing stays in [`DirState::missing_docs`] and gets retried on our next pass.
+    async fn fetch_batch(&self, ids: &[DocId]) -> Result<Vec<(DocId, String)>>;
+}
+
+/// Something that wants to hear about bootstrap progress as it happens, rather than only once
+/// [`load_with_policy`] returns.
+///
+/// A malformed cache file can make a state claim progress (`add_from_cache` returning `true`)
+/// for a long time before [`load_with_policy`] either advances it or gives up, so a caller
+/// driving a UI off bootstrap progress needs updates from inside that loop, not just a single
+/// report at the end.
+#[async_trait]
+pub(crate) trait ProgressSink: Send + Sync {
+    /// Called after each iteration of `load_with_policy`'s loop that changed the state without
+    /// letting it advance, and again each time the state advances, with the state's progress as
+    /// of then. An iteration that changes nothing reports nothing.
+    async fn report(&self, progress: DirProgress);
+
+    /// Called when `load_with_policy` notices that progress has been stuck at the same
+    /// [`DirProgress`] for longer than [`DEFAULT_STALL_TIMEOUT`], with documents missing that the
+    /// state says can never be resolved from the cache.
+    ///
+    /// Whether this fires before [`load_with_policy`] itself gives up with [`Error::Stalled`]
+    /// depends on how long its passes take: the retry budget counts passes that changed the
+    /// state, while this waits for [`DEFAULT_STALL_TIMEOUT`] of wall-clock time.
+    async fn report_blockage(&self, blockage: DirBlockage);
+}
 
 /// Try tp update `state` by loading cached information from `dirmgr`.
 /// Return true if anything changed.
 async fn load_once(
+    attempt: AttemptId,
     state: &mut Box<dyn DirState>,
     docdir: &str
 ) -> Result<bool> {
@@ -18,33 +89,233 @@ async fn load_once(
     if missing.is_empty() {
         Ok(false)
     } else {
+        trace!("attempt {}: loading {} from cache", attempt, state.describe());
         state.add_from_cache(&docdir)
     }
 }
 
-/// Try to load as much state as possible for a provided `state` from the
-/// cache in `dirmgr`, advancing the state to the extent possible.
+/// Ask `client` for everything in `state.missing_docs()`, with concurrency and per-document retry
+/// governed by `state`'s own [`DirState::retry_schedule`], and feed whatever comes back into the
+/// state through [`DirState::add_from_download`]. Return true if anything changed.
+async fn download_once(
+    attempt: AttemptId,
+    state: &mut Box<dyn DirState>,
+    client: &dyn DirClient,
+) -> Result<bool> {
+    let missing = state.missing_docs();
+    if missing.is_empty() {
+        return Ok(false);
+    }
+
+    trace!("attempt {}: downloading {} from network", attempt, state.describe());
+    let results = fetch_multiple(client, &missing, &state.retry_schedule()).await;
+    if results.is_empty() {
+        return Ok(false);
+    }
+    state.add_from_download(&results)
+}
+
+/// Fetch every document in `missing` from `client`, running up to `schedule.parallelism()`
+/// requests concurrently, and retrying a document that comes back empty with `schedule`'s
+/// exponential backoff (plus jitter) until its attempt budget is spent.
 ///
-/// No downloads are performed; the provided state will not be reset.
+/// Returns whatever came back successfully; a document that is never obtained within its attempt
+/// budget is simply left out, so the rest of the batch still makes forward progress.
+async fn fetch_multiple(
+    client: &dyn DirClient,
+    missing: &[DocId],
+    schedule: &DownloadSchedule,
+) -> Vec<(DocId, String)> {
+    let mut queue = missing.iter().copied();
+    let mut pending = FuturesUnordered::new();
+    for id in queue.by_ref().take(schedule.parallelism() as usize) {
+        pending.push(fetch_with_retry(client, id, *schedule));
+    }
+
+    let mut results = Vec::with_capacity(missing.len());
+    while let Some((id, text)) = pending.next().await {
+        if let Some(text) = text {
+            results.push((id, text));
+        }
+        if let Some(next_id) = queue.next() {
+            pending.push(fetch_with_retry(client, next_id, *schedule));
+        }
+    }
+    results
+}
+
+/// Fetch a single `id` from `client`, retrying with `schedule`'s exponential backoff (plus up to
+/// 50% jitter, so a batch of documents that all failed together doesn't retry in lockstep) until
+/// `schedule.attempts()` is spent. Returns `None` once the budget runs out rather than an error,
+/// so one unobtainable document doesn't abort the rest of a [`fetch_multiple`] call.
+async fn fetch_with_retry(
+    client: &dyn DirClient,
+    id: DocId,
+    schedule: DownloadSchedule,
+) -> (DocId, Option<String>) {
+    for attempt in 1..=schedule.attempts() {
+        match client.fetch_batch(&[id]).await {
+            Ok(results) => {
+                if let Some((_, text)) = results.into_iter().next() {
+                    return (id, Some(text));
+                }
+            }
+            Err(e) => debug!("attempt {}: fetching {:?} failed: {}", attempt, id, e),
+        }
+
+        if attempt < schedule.attempts() {
+            let base = schedule.delay_before(attempt + 1);
+            let jitter = Duration::from_millis(rand::thread_rng().gen_range(0..=base.as_millis() as u64 / 2 + 1));
+            sleep(base + jitter).await;
+        }
+    }
+    (id, None)
+}
+
+/// How [`load_with_policy`] should react when a state's [`DirState::reset_time`] has already
+/// passed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ExpiryPolicy {
+    /// Ignore expiry and keep trying to advance the state we were given, even once its validity
+    /// window has elapsed. This is [`load`]'s long-standing behavior, which the function's own
+    /// docs describe as "the provided state will not be reset" -- kept as-is so existing callers
+    /// are unaffected.
+    BestEffort,
+    /// Reset and start over from the initial state, using the same cached documents, once
+    /// `reset_time()` has passed, rather than advancing (or returning) an already-expired
+    /// directory.
+    ResetOnExpiry,
+}
+
+/// Try to load as much state as possible for a provided `state` from the cache in `dirmgr`,
+/// downloading anything still missing through `client` (if given), and advancing the state to
+/// the extent possible.
+///
+/// A pass that changes nothing ends the loop and returns the state as it is. Each pass that
+/// changes the state without letting it advance counts as one attempt against that state's
+/// [`DirState::retry_schedule`]. Once the budget is exhausted, what happens next depends on
+/// whether the state thinks its own [`DirState::missing_docs`] can ever be resolved: if they can
+/// (the cache or network is just slow), the state is reset and tried again from scratch; if they
+/// can't ([`DirState::missing_docs_unsatisfiable`]), this returns [`Error::Stalled`] instead of
+/// resetting forever -- see the `TODO SECURITY` note on `GetCertsState`.
+///
+/// The provided state will not be reset merely because it has expired; use
+/// [`load_with_policy`] with [`ExpiryPolicy::ResetOnExpiry`] for that.
 pub(crate) async fn load(
+    state: Box<dyn DirState>,
+    docdir: &str,
+    client: Option<&dyn DirClient>,
+) -> Result<Box<dyn DirState>> {
+    load_with_policy(state, docdir, client, ExpiryPolicy::BestEffort, None).await
+}
+
+/// As [`load`], but lets the caller choose what happens once `state.reset_time()` has passed via
+/// `policy`, and optionally hear about progress from inside the loop via `progress` (see
+/// [`ProgressSink::report`] for which iterations report).
+pub(crate) async fn load_with_policy(
     mut state: Box<dyn DirState>,
-    docdir: &str
+    docdir: &str,
+    client: Option<&dyn DirClient>,
+    policy: ExpiryPolicy,
+    progress: Option<&dyn ProgressSink>,
 ) -> Result<Box<dyn DirState>> {
-    let mut safety_counter = 0_usize;
+    let id = AttemptId::new();
+    let mut tracker = RetryTracker::default();
+    let mut clock = ProgressClock::new(state.bootstrap_progress(), SystemTime::now());
     loop {
-        let changed = load_once(&mut state, &docdir).await?;
+        if policy == ExpiryPolicy::ResetOnExpiry {
+            if let Some(reset_time) = state.reset_time() {
+                if SystemTime::now() >= reset_time {
+                    trace!(
+                        "attempt {}: {} expired at {:?}; resetting",
+                        id,
+                        state.describe(),
+                        reset_time
+                    );
+                    state = state.reset()?;
+                    tracker.reset();
+                    continue;
+                }
+            }
+        }
+
+        let mut changed = load_once(id, &mut state, &docdir).await?;
+
+        if !state.can_advance() {
+            if let Some(client) = client {
+                changed |= download_once(id, &mut state, client).await?;
+            }
+        }
+
+        let now = SystemTime::now();
+        clock.observe(state.bootstrap_progress(), now);
+        if let Some(progress) = progress {
+            if let Some(blockage) = DirBlockage::detect(
+                &state.bootstrap_progress(),
+                clock.stalled_for(now),
+                DEFAULT_STALL_TIMEOUT,
+                state.missing_docs_unsatisfiable(),
+            ) {
+                progress.report_blockage(blockage).await;
+            }
+        }
+
+        if changed && !state.can_advance() {
+            if let Some(progress) = progress {
+                progress.report(state.bootstrap_progress()).await;
+            }
+        }
 
         if state.can_advance() {
+            trace!("attempt {}: advancing past {}", id, state.describe());
             state = state.advance()?;
-            safety_counter = 0;
-        } else {
-            if !changed {
-                break;
+            tracker.reset();
+            if let Some(progress) = progress {
+                progress.report(state.bootstrap_progress()).await;
             }
-            safety_counter += 1;
-            if safety_counter == 100 {
-                panic!("Spent 100 iterations in the same state: this is a bug");
+            continue;
+        }
+
+        if !changed {
+            trace!("attempt {}: no progress on {}; stopping", id, state.describe());
+            break;
+        }
+
+        let schedule = state.retry_schedule();
+        let retry_attempt = tracker.begin_attempt();
+        debug!(
+            "attempt {}: {}: retry {}/{}, next delay {:?}",
+            id,
+            state.describe(),
+            retry_attempt,
+            schedule.attempts(),
+            schedule.delay_before(retry_attempt + 1),
+        );
+
+        if tracker.exhausted(&schedule) {
+            if state.missing_docs_unsatisfiable() {
+                warn!(
+                    "attempt {}: {}: giving up after {} retries with unresolvable missing documents",
+                    id,
+                    state.describe(),
+                    retry_attempt
+                );
+                return Err(Error::Stalled {
+                    state_description: state.describe(),
+                    missing: state.missing_docs(),
+                }
+                .into());
             }
+
+            warn!(
+                "attempt {}: {}: giving up after {} retries with no advance; resetting",
+                id,
+                state.describe(),
+                retry_attempt
+            );
+            trace!("attempt {}: resetting {}", id, state.describe());
+            state = state.reset()?;
+            tracker.reset();
         }
     }
 
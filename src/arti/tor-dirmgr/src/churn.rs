@@ -0,0 +1,37 @@
+//! Parsing for relay-churn updates, used by [`crate::DirMgr::apply_churn`] to learn about relays
+//! that have joined or left the network since the current consensus was published.
+
+use tor_llcrypto::pk::rsa::RsaIdentity;
+
+use crate::{err::DocSource, Error, Result};
+
+/// Parse a churn document: one hex-encoded relay RSA identity fingerprint per line. Blank lines
+/// are ignored.
+///
+/// This is the same format [`crate`]'s sibling flat-file directory manager already reads from its
+/// own `churn.txt`.
+pub(crate) fn parse_churn(text: &str) -> Result<Vec<RsaIdentity>> {
+    let ids: std::result::Result<Vec<RsaIdentity>, Error> = text
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let bytes = decode_hex(line)
+                .ok_or_else(|| Error::from_netdoc(DocSource::Churn, "not valid hex"))?;
+            RsaIdentity::from_bytes(&bytes)
+                .ok_or_else(|| Error::from_netdoc(DocSource::Churn, "invalid RSA identity"))
+        })
+        .collect();
+    Ok(ids?)
+}
+
+/// Decode `text` as a string of hex digit pairs, or return `None` if it has an odd length or
+/// contains anything else.
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
@@ -0,0 +1,97 @@
+//! A source of wall-clock time and randomness for the directory state machine.
+//!
+//! Before this module existed, [`crate::state`] called `SystemTime::now()` and
+//! `rand::thread_rng()` directly wherever it needed to check a document's validity or pick a
+//! random replacement time, which made `reset_time`, consensus-freshness checks, and the
+//! randomized download instant impossible to reproduce in a test or drive from a simulated clock.
+//! A [`DirClock`] is handed to [`crate::state::GetConsensusState::new`] instead, so every
+//! downstream decision goes through it.
+
+use std::fmt::Debug;
+use std::time::{Duration, SystemTime};
+
+use rand::Rng;
+
+/// A source of the current time and of randomness, for code that would otherwise call
+/// `SystemTime::now()` and `rand::thread_rng()` directly.
+pub trait DirClock: Debug + Send + Sync {
+    /// Return the current wall-clock time.
+    fn now(&self) -> SystemTime;
+
+    /// Return a uniformly distributed [`Duration`] in `0..bound`, or `Duration::ZERO` if `bound`
+    /// is zero.
+    fn random_duration(&self, bound: Duration) -> Duration;
+}
+
+/// The default [`DirClock`]: the real wall clock and a thread-local RNG.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemDirClock;
+
+impl DirClock for SystemDirClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn random_duration(&self, bound: Duration) -> Duration {
+        if bound == Duration::ZERO {
+            return Duration::ZERO;
+        }
+        rand::thread_rng().gen_range(Duration::ZERO..bound)
+    }
+}
+
+/// A [`DirClock`] that always reports the same instant and the same "random" duration, for
+/// deterministic tests and simulated-clock embedders.
+#[derive(Clone, Copy, Debug)]
+pub struct FixedDirClock {
+    /// The instant [`DirClock::now`] reports.
+    now: SystemTime,
+    /// The duration [`DirClock::random_duration`] reports, clamped to whatever `bound` it's
+    /// asked for.
+    next_random: Duration,
+}
+
+impl FixedDirClock {
+    /// Build a clock fixed at `now`, whose `random_duration` always returns `next_random`
+    /// (clamped to the requested bound).
+    pub fn new(now: SystemTime, next_random: Duration) -> Self {
+        FixedDirClock { now, next_random }
+    }
+}
+
+impl DirClock for FixedDirClock {
+    fn now(&self) -> SystemTime {
+        self.now
+    }
+
+    fn random_duration(&self, bound: Duration) -> Duration {
+        self.next_random.min(bound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_dir_clock_reports_a_fixed_now() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let clock = FixedDirClock::new(now, Duration::ZERO);
+        assert_eq!(clock.now(), now);
+        assert_eq!(clock.now(), now, "repeated calls must keep reporting the same instant");
+    }
+
+    #[test]
+    fn fixed_dir_clock_clamps_random_duration_to_the_requested_bound() {
+        let clock = FixedDirClock::new(SystemTime::UNIX_EPOCH, Duration::from_secs(100));
+        assert_eq!(
+            clock.random_duration(Duration::from_secs(10)),
+            Duration::from_secs(10),
+            "a bound smaller than next_random must still be respected"
+        );
+        assert_eq!(
+            clock.random_duration(Duration::from_secs(1000)),
+            Duration::from_secs(100)
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! Configuration for the local directory manager: which authorities to trust, which network
+//! parameters to override, and how aggressively to retry a download that stalls.
+
+// Code mostly copied from Arti.
+
+use tor_netdir::params::NetParameters;
+use tor_netdoc::doc::netstatus::ConsensusFlavor;
+
+use crate::authority::Authority;
+use crate::retry::DownloadSchedule;
+
+/// Retry policy for each kind of document this directory manager fetches.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DownloadScheduleConfig {
+    /// Retry policy for the consensus.
+    pub retry_consensus: DownloadSchedule,
+    /// Retry policy for authority certificates.
+    pub retry_certs: DownloadSchedule,
+    /// Retry policy for microdescriptors.
+    pub retry_microdescs: DownloadSchedule,
+}
+
+/// The directory authorities to trust. There is no separate signature threshold: a consensus
+/// must be signed by more than half of these authorities.
+#[derive(Clone, Debug, Default)]
+pub struct NetworkConfig {
+    /// The authorities trusted to sign a consensus.
+    pub authorities: Vec<Authority>,
+}
+
+/// Configuration for a [`crate::DirMgr`].
+#[derive(Clone, Debug)]
+pub struct NetDirConfig {
+    /// Authority and network information.
+    network: NetworkConfig,
+    /// Network parameters to apply on top of whatever the consensus itself specifies.
+    override_net_params: NetParameters,
+    /// Retry policy for each kind of document this directory manager fetches.
+    schedule: DownloadScheduleConfig,
+    /// Which consensus flavor to fetch and validate.
+    ///
+    /// Note: only [`ConsensusFlavor::Microdesc`] can currently complete bootstrap. Selecting
+    /// [`ConsensusFlavor::Ns`] lets [`crate::state::GetConsensusState`] fetch the full
+    /// router-descriptor consensus, but it will then be rejected with
+    /// [`crate::Error::UnsupportedConsensusFlavor`], since `tor_netdir`'s `PartialNetDir` has no
+    /// way to build a usable `NetDir` from full router descriptors.
+    consensus_flavor: ConsensusFlavor,
+}
+
+impl NetDirConfig {
+    /// Start building a [`NetDirConfig`].
+    pub fn builder() -> NetDirConfigBuilder {
+        NetDirConfigBuilder::default()
+    }
+
+    /// Return the authorities this configuration trusts.
+    pub fn authorities(&self) -> &[Authority] {
+        &self.network.authorities
+    }
+
+    /// Return the network parameters to apply on top of the consensus's own.
+    pub fn override_net_params(&self) -> NetParameters {
+        self.override_net_params.clone()
+    }
+
+    /// Return the retry policy to use for each kind of document.
+    pub fn schedule(&self) -> &DownloadScheduleConfig {
+        &self.schedule
+    }
+
+    /// Return the consensus flavor to fetch and validate.
+    pub fn consensus_flavor(&self) -> ConsensusFlavor {
+        self.consensus_flavor
+    }
+}
+
+/// Builder for [`NetDirConfig`].
+#[derive(Clone, Debug)]
+pub struct NetDirConfigBuilder {
+    /// Authorities trusted to sign a consensus.
+    authorities: Vec<Authority>,
+    /// Network parameters to override.
+    override_net_params: NetParameters,
+    /// Retry policy for each kind of document.
+    schedule: DownloadScheduleConfig,
+    /// Consensus flavor to fetch and validate.
+    consensus_flavor: ConsensusFlavor,
+}
+
+impl Default for NetDirConfigBuilder {
+    fn default() -> Self {
+        NetDirConfigBuilder {
+            authorities: Vec::new(),
+            override_net_params: NetParameters::default(),
+            schedule: DownloadScheduleConfig::default(),
+            consensus_flavor: ConsensusFlavor::Microdesc,
+        }
+    }
+}
+
+impl NetDirConfigBuilder {
+    /// Set the authorities to trust.
+    pub fn authorities(&mut self, authorities: Vec<Authority>) -> &mut Self {
+        self.authorities = authorities;
+        self
+    }
+
+    /// Set the network parameters to override.
+    pub fn override_net_params(&mut self, params: NetParameters) -> &mut Self {
+        self.override_net_params = params;
+        self
+    }
+
+    /// Set the retry policy for each kind of document.
+    pub fn schedule(&mut self, schedule: DownloadScheduleConfig) -> &mut Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// Set which consensus flavor to fetch: the compact microdescriptor-based one (the default),
+    /// or the full router-descriptor ("NS") one for deployments that need the extra per-relay
+    /// information only full descriptors carry.
+    ///
+    /// See the limitation noted on [`NetDirConfig::consensus_flavor`]: only
+    /// [`ConsensusFlavor::Microdesc`] can currently complete bootstrap.
+    pub fn consensus_flavor(&mut self, flavor: ConsensusFlavor) -> &mut Self {
+        self.consensus_flavor = flavor;
+        self
+    }
+
+    /// Build the [`NetDirConfig`].
+    pub fn build(&self) -> anyhow::Result<NetDirConfig> {
+        Ok(NetDirConfig {
+            network: NetworkConfig {
+                authorities: self.authorities.clone(),
+            },
+            override_net_params: self.override_net_params.clone(),
+            schedule: self.schedule,
+            consensus_flavor: self.consensus_flavor,
+        })
+    }
+}
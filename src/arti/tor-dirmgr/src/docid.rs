@@ -0,0 +1,37 @@
+//! Identifiers for the directory documents [`crate::DirState`] can be missing and
+//! [`crate::bootstrap`] can fetch -- the vocabulary both sides of the bootstrap loop share instead
+//! of passing raw paths or resource strings around.
+
+use tor_netdoc::doc::authcert::AuthCertKeyIds;
+use tor_netdoc::doc::microdesc::MdDigest;
+use tor_netdoc::doc::netstatus::ConsensusFlavor;
+
+/// How a [`crate::DirState`] is allowed to satisfy a document it's missing: from the cache only,
+/// from the cache with a network fallback, or straight from the network.
+#[derive(Clone, Copy, Debug)]
+pub enum CacheUsage {
+    /// Only look in the cache; never download.
+    CacheOnly,
+    /// Prefer the cache, but download if the cache doesn't have what's needed.
+    CacheOkay,
+    /// Ask for a fresh download. The loaders do not skip the cache for this variant: the cache
+    /// is still read first, and a download happens only if a network client is configured.
+    MustDownload,
+}
+
+/// A reference to a single directory document, independent of where it ultimately comes from
+/// (cache or network).
+#[derive(Clone, Copy, Debug)]
+pub enum DocId {
+    /// The latest consensus of the given flavor, subject to `cache_usage`.
+    LatestConsensus {
+        /// Which consensus flavor to fetch.
+        flavor: ConsensusFlavor,
+        /// Whether the cache, the network, or both are allowed to satisfy this request.
+        cache_usage: CacheUsage,
+    },
+    /// An authority certificate, identified by its signing and identity key fingerprints.
+    AuthCert(AuthCertKeyIds),
+    /// A microdescriptor, identified by its digest.
+    Microdesc(MdDigest),
+}
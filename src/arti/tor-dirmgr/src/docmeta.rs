@@ -0,0 +1,42 @@
+//! Metadata about a consensus document, captured once at parse time.
+//!
+//! [`GetCertsState`](crate::state) and [`GetMicrodescsState`](crate::state) only ever need a
+//! consensus's validity window (to compute `reset_time`, and to pick the next download instant
+//! once a directory is complete) -- not the whole parsed document -- so this is carried forward
+//! through the state machine instead of the consensus itself.
+
+use tor_netdoc::doc::netstatus::{Lifetime, UnvalidatedMdConsensus};
+
+/// A consensus's validity window, recorded when the consensus is first parsed and carried along
+/// by every state that still needs it.
+#[derive(Clone, Debug)]
+pub(crate) struct ConsensusMeta {
+    /// When this consensus is valid-after, fresh-until, and valid-until.
+    lifetime: Lifetime,
+}
+
+impl ConsensusMeta {
+    /// Build metadata for a consensus that has just passed
+    /// [`Timebound::check_valid_at`](tor_checkable::Timebound::check_valid_at), from the signed
+    /// and unsigned portions [`tor_netdoc::doc::netstatus::MdConsensus::parse`] split the document
+    /// text into.
+    ///
+    /// `_signedval` and `_remainder` aren't needed for anything this simplified client does with a
+    /// consensus's metadata, but are taken anyway to mirror the upstream signature this was
+    /// adapted from, in case a future caller wants to record them (e.g. to detect a cache that was
+    /// edited after being written).
+    pub(crate) fn from_unvalidated(
+        _signedval: &str,
+        _remainder: &str,
+        timely: &UnvalidatedMdConsensus,
+    ) -> Self {
+        ConsensusMeta {
+            lifetime: timely.peek_lifetime().clone(),
+        }
+    }
+
+    /// This consensus's validity window.
+    pub(crate) fn lifetime(&self) -> &Lifetime {
+        &self.lifetime
+    }
+}
@@ -0,0 +1,103 @@
+//! Declare an error type for the tor-dirmgr crate.
+
+// Code mostly copied from Arti.
+
+use std::path::PathBuf;
+
+use thiserror::Error;
+use tor_netdoc::doc::netstatus::ConsensusFlavor;
+
+use crate::DocId;
+
+/// Identifies which piece of directory state a parse or validation failure came from.
+///
+/// Every place this crate reads a document -- a cached consensus, a side-loaded authority
+/// certificate, a batch of microdescriptors -- now tags the error it produces with one of these,
+/// so that a single [`Error::NetDocError`] no longer means "something, somewhere, didn't parse".
+#[derive(Clone, Debug)]
+pub enum DocSource {
+    /// The consensus document.
+    Consensus(PathBuf),
+    /// An authority certificate.
+    Certificate(PathBuf),
+    /// A batch of microdescriptors.
+    Microdescs(PathBuf),
+    /// A document fetched over the network rather than read from the cache, identified by a
+    /// human-readable description of the request that produced it (usually a `DocId`'s `Debug`
+    /// form, since requests have no path to report).
+    Download(String),
+    /// A relay-churn update, applied via [`crate::DirMgr::apply_churn`] rather than read from a
+    /// file, so there's no path to report either.
+    Churn,
+}
+
+impl std::fmt::Display for DocSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DocSource::Consensus(p) => write!(f, "consensus ({})", p.to_string_lossy()),
+            DocSource::Certificate(p) => write!(f, "certificate ({})", p.to_string_lossy()),
+            DocSource::Microdescs(p) => write!(f, "microdescriptors ({})", p.to_string_lossy()),
+            DocSource::Download(description) => write!(f, "download ({})", description),
+            DocSource::Churn => write!(f, "churn update"),
+        }
+    }
+}
+
+/// An error originated by the directory manager code
+#[derive(Error, Clone, Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// We don't have enough directory information to build circuits.
+    #[error("directory not present")]
+    DirectoryNotPresent,
+
+    /// The directory manager was dropped before we could use it.
+    #[error("directory manager was dropped")]
+    ManagerDropped,
+
+    /// A consensus claimed to be signed by authorities we don't recognize.
+    #[error("consensus signed by unrecognized authorities")]
+    UnrecognizedAuthorities,
+
+    /// The network configuration we were given couldn't be used.
+    #[error("bad network configuration: {0}")]
+    BadNetworkConfig(&'static str),
+
+    /// A document failed to parse or validate, tagged with where it came from.
+    #[error("could not process {source}: {error}")]
+    NetDocError {
+        /// Which document this error came from.
+        source: DocSource,
+        /// The underlying parse or validation failure, as text (the originating error types
+        /// aren't `Clone`, which this enum needs to be).
+        error: String,
+    },
+
+    /// We were configured to fetch a [`ConsensusFlavor`] this manager has no code path to turn
+    /// into a usable `NetDir` -- currently only [`ConsensusFlavor::Microdesc`] is supported.
+    #[error("unsupported consensus flavor: {0:?}")]
+    UnsupportedConsensusFlavor(ConsensusFlavor),
+
+    /// Bootstrap got stuck: the state described by `state_description` has been retried past its
+    /// schedule's attempt budget, and it told us the documents it's still missing can never be
+    /// resolved from the cache. Returned instead of resetting and retrying forever.
+    #[error("bootstrap stalled on {state_description}: missing {missing:?}")]
+    Stalled {
+        /// What the stuck state was doing, from [`crate::DirState::describe`].
+        state_description: String,
+        /// The documents it's still missing, from [`crate::DirState::missing_docs`].
+        missing: Vec<DocId>,
+    },
+}
+
+impl Error {
+    /// Wrap `error` as a [`Error::NetDocError`] tagged with `source`, preserving the original
+    /// message so the eventual log line still says *why* the document was rejected, not just
+    /// *which* one.
+    pub(crate) fn from_netdoc(source: DocSource, error: impl std::fmt::Display) -> Self {
+        Error::NetDocError {
+            source,
+            error: error.to_string(),
+        }
+    }
+}
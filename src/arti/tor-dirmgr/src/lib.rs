@@ -23,29 +23,70 @@
 
 pub mod authority;
 mod bootstrap;
+mod churn;
+mod clock;
 mod config;
 mod docid;
 mod docmeta;
 mod err;
+mod netclient;
 mod retry;
 mod shared_ref;
 mod state;
+mod status;
+mod store;
 
 use crate::docid::CacheUsage;
 use crate::shared_ref::SharedMutArc;
+use tor_circmgr::CircMgr;
 use tor_netdir::NetDir;
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use futures::{Stream, StreamExt};
 use log::{info, warn};
+use postage::{sink::Sink, watch};
+use tor_rtcompat::scheduler::{TaskHandle, TaskSchedule};
+use tor_rtcompat::Runtime;
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::SystemTime;
 
 pub use authority::Authority;
+pub use clock::{DirClock, FixedDirClock, SystemDirClock};
 pub use config::{DownloadScheduleConfig, NetDirConfig, NetDirConfigBuilder, NetworkConfig};
 pub use docid::DocId;
-pub use err::Error;
+pub use err::{DocSource, Error};
+pub use status::{DirBlockage, DirBootstrapStatus, DirProgress};
+pub use store::{SideloadStore, SqliteStore, Store};
+
+/// Adapts a [`watch::Sender<DirBootstrapStatus>`] and a [`DirMgr`]'s `blockage` slot into a
+/// [`bootstrap::ProgressSink`], so [`bootstrap::load_with_policy`] can publish progress -- and
+/// report a stall -- as they happen, rather than `DirMgr` only hearing about either once the
+/// whole load finishes (or fails).
+struct StatusReporter<'a> {
+    /// Where to publish each [`DirBootstrapStatus`] update.
+    status_tx: watch::Sender<DirBootstrapStatus>,
+    /// The owning [`DirMgr`]'s blockage slot, so a stall detected mid-load is visible to
+    /// [`DirMgr::bootstrap_blockage`] immediately, rather than only once (if ever) the load gives
+    /// up and returns [`Error::Stalled`].
+    blockage: &'a Mutex<Option<DirBlockage>>,
+}
+
+#[async_trait]
+impl<'a> bootstrap::ProgressSink for StatusReporter<'a> {
+    async fn report(&self, progress: status::DirProgress) {
+        let _ = self
+            .status_tx
+            .clone()
+            .send(DirBootstrapStatus::new(progress))
+            .await;
+    }
+
+    async fn report_blockage(&self, blockage: DirBlockage) {
+        *self.blockage.lock().expect("blockage lock poisoned") = Some(blockage);
+    }
+}
 
 /// A directory manager to download, fetch, and cache a Tor directory.
 ///
@@ -77,6 +118,38 @@ pub struct DirMgr {
     /// users, and replace it once a new directory is bootstrapped.
     netdir: SharedMutArc<NetDir>,
 
+    /// The sending half of the bootstrap-status channel; each call to `load_directory` publishes
+    /// the resulting state's progress here. Kept alongside `netdir` rather than folded into it,
+    /// since a caller may want to watch progress well before there is a `NetDir` to hand out.
+    status_tx: watch::Sender<DirBootstrapStatus>,
+
+    /// A receiver kept alive purely so that [`DirMgr::bootstrap_events`] can hand out fresh
+    /// clones; `postage::watch` closes a channel once every receiver has been dropped.
+    status_rx: watch::Receiver<DirBootstrapStatus>,
+
+    /// Where we read and write cached directory documents.
+    store: Mutex<Box<dyn Store>>,
+
+    /// A way to download documents over the network, if we have one. When this is `None`, we
+    /// only ever read from the cache (the "offline" mode described above); when it's `Some`, a
+    /// load that can't be satisfied from the cache falls back to downloading.
+    client: Option<Arc<dyn bootstrap::DirClient>>,
+
+    /// Set if our last [`DirMgr::load_directory`] call came back with [`Error::Stalled`], or if
+    /// a stall was reported through [`StatusReporter`] while a load was still running, so
+    /// [`DirMgr::bootstrap_blockage`] can report why without callers needing to hold on to (or
+    /// parse) that error themselves. Cleared on the next call that makes it past bootstrap.
+    blockage: Mutex<Option<DirBlockage>>,
+
+    /// The reset time of our current directory state, if we have one, kept around so
+    /// [`DirMgr::keep_directory_fresh`] knows how long to wait before the next reload without
+    /// needing the (already-consumed) `DirState` itself.
+    next_reset: Mutex<Option<SystemTime>>,
+
+    /// The source of time and randomness handed to each [`state::GetConsensusState`], so an
+    /// embedder can replace [`SystemDirClock`] with a simulated clock instead of this manager's
+    /// time-dependent decisions always going through the real one.
+    clock: Arc<dyn DirClock>,
 }
 
 impl DirMgr {
@@ -117,13 +190,29 @@ impl DirMgr {
         Ok(dirmgr.netdir())
     }
 
+    /// As [`DirMgr::load_or_bootstrap_once`], but actually bootstraps from the Tor network
+    /// through `circmgr` when the cache at `docdir` is missing or expired, instead of only ever
+    /// reading from it.
+    pub async fn load_or_bootstrap_with_circuits<R: Runtime>(
+        config: NetDirConfig,
+        docdir: &str,
+        circmgr: Arc<CircMgr<R>>,
+    ) -> Result<Arc<NetDir>> {
+        let dirmgr = DirMgr::bootstrap_from_config_with_circuits(config, &docdir, circmgr).await?;
+        Ok(dirmgr.netdir())
+    }
+
     /// Return a new directory manager from a given configuration,
     /// bootstrapping from the network as necessary.
     ///
     /// This function will to return until the directory is
-    /// bootstrapped enough to build circuits.  It will also launch a
-    /// background task that fetches any missing information, and that
-    /// replaces the directory when a new one is available.
+    /// bootstrapped enough to build circuits.
+    ///
+    /// Unlike what the name might suggest, this alone does not launch any background task to keep
+    /// the directory fresh once loaded -- with no network client configured there is nothing for
+    /// such a task to do beyond what [`DirMgr::keep_directory_fresh`] already offers against the
+    /// cache alone. Use [`DirMgr::bootstrap_and_keep_fresh`] for a version that both bootstraps
+    /// over the network and spawns that background task in one call.
     pub async fn bootstrap_from_config(
         config: NetDirConfig,
         docdir: &str,
@@ -141,26 +230,230 @@ impl DirMgr {
         Ok(dirmgr)
     }
 
-    /// Construct a DirMgr from a NetDirConfig.
+    /// As [`DirMgr::bootstrap_from_config`], but downloads anything missing from the cache over
+    /// Tor circuits built through `circmgr`, instead of giving up once the cache runs out.
+    ///
+    /// # Limitations
+    ///
+    /// Building a directory circuit needs *some* [`NetDir`] to pick a cache from, even a stale
+    /// one; this manager keeps no fallback-directory list, so it cannot bootstrap a circuit from
+    /// a completely empty cache. The very first run still needs a side-loaded consensus at
+    /// `docdir` to get off the ground -- see [`netclient::TorDirClient`].
+    pub async fn bootstrap_from_config_with_circuits<R: Runtime>(
+        config: NetDirConfig,
+        docdir: &str,
+        circmgr: Arc<CircMgr<R>>,
+    ) -> Result<Arc<Self>> {
+        let dirmgr = Arc::new(DirMgr::from_config_with_circuits(config, circmgr)?);
+
+        dirmgr
+            .load_directory(&docdir)
+            .await
+            .context("Error loading cached directory")?;
+
+        info!("We have enough information to build circuits.");
+
+        Ok(dirmgr)
+    }
+
+    /// As [`DirMgr::bootstrap_from_config_with_circuits`], but takes wall-clock time and
+    /// randomness from `clock` instead of [`SystemDirClock`], so a test or an embedder simulating
+    /// time can drive the exact same consensus-validity checks and randomized replacement-time
+    /// choice that bootstrap makes against the real clock.
+    pub async fn bootstrap_from_config_with_clock<R: Runtime>(
+        config: NetDirConfig,
+        docdir: &str,
+        circmgr: Arc<CircMgr<R>>,
+        clock: Arc<dyn DirClock>,
+    ) -> Result<Arc<Self>> {
+        let netdir = SharedMutArc::new();
+        let client = Arc::new(netclient::TorDirClient::new(circmgr, netdir.clone()));
+        let dirmgr = Arc::new(DirMgr::from_config_with_client_and_netdir(
+            config,
+            Box::new(SideloadStore::new()),
+            Some(client),
+            netdir,
+            clock,
+        )?);
+
+        dirmgr
+            .load_directory(&docdir)
+            .await
+            .context("Error loading cached directory")?;
+
+        info!("We have enough information to build circuits.");
+
+        Ok(dirmgr)
+    }
+
+    /// As [`DirMgr::bootstrap_from_config_with_circuits`], but also spawns the background refresh
+    /// task via [`DirMgr::keep_directory_fresh`], so the directory keeps itself current as its
+    /// consensus nears expiry instead of only ever reflecting this one bootstrap -- a task that
+    /// [`DirMgr::bootstrap_from_config`] alone never starts.
+    ///
+    /// Returns the task's [`TaskHandle`] alongside the manager, so the embedder can suspend,
+    /// resume, or drop the refresh task independently of the `DirMgr` itself.
+    pub async fn bootstrap_and_keep_fresh<R: Runtime>(
+        config: NetDirConfig,
+        docdir: &str,
+        runtime: R,
+        circmgr: Arc<CircMgr<R>>,
+    ) -> Result<(Arc<Self>, TaskHandle)> {
+        let dirmgr = DirMgr::bootstrap_from_config_with_circuits(config, docdir, circmgr).await?;
+        let handle = dirmgr.keep_directory_fresh(runtime, docdir.to_string());
+        Ok((dirmgr, handle))
+    }
+
+    /// Construct a DirMgr from a NetDirConfig, reading and writing its cache through a
+    /// [`SideloadStore`] over the flat files at `docdir`, with no network download capability.
     fn from_config(
         config: NetDirConfig,
+    ) -> Result<Self> {
+        Self::from_config_with_store(config, Box::new(SideloadStore::new()))
+    }
+
+    /// Construct a DirMgr from a NetDirConfig, reading and writing its cache through a
+    /// [`SideloadStore`], and downloading anything the cache can't supply over Tor circuits built
+    /// through `circmgr`.
+    fn from_config_with_circuits<R: Runtime>(
+        config: NetDirConfig,
+        circmgr: Arc<CircMgr<R>>,
     ) -> Result<Self> {
         let netdir = SharedMutArc::new();
+        let client = Arc::new(netclient::TorDirClient::new(circmgr, netdir.clone()));
+        Self::from_config_with_client_and_netdir(
+            config,
+            Box::new(SideloadStore::new()),
+            Some(client),
+            netdir,
+            Arc::new(SystemDirClock),
+        )
+    }
+
+    /// Construct a DirMgr from a NetDirConfig, reading and writing its cache through `store`,
+    /// with no network download capability.
+    ///
+    /// Use this instead of [`DirMgr::from_config`] to back the cache with a [`SqliteStore`]
+    /// instead of the default flat-file [`SideloadStore`].
+    fn from_config_with_store(config: NetDirConfig, store: Box<dyn Store>) -> Result<Self> {
+        Self::from_config_with_client(config, store, None)
+    }
+
+    /// Construct a DirMgr from a NetDirConfig, reading and writing its cache through `store`,
+    /// and downloading anything the cache can't supply through `client`.
+    fn from_config_with_client(
+        config: NetDirConfig,
+        store: Box<dyn Store>,
+        client: Option<Arc<dyn bootstrap::DirClient>>,
+    ) -> Result<Self> {
+        Self::from_config_with_client_and_netdir(
+            config,
+            store,
+            client,
+            SharedMutArc::new(),
+            Arc::new(SystemDirClock),
+        )
+    }
+
+    /// As [`DirMgr::from_config_with_client`], but reuses `netdir` instead of starting from an
+    /// empty one, so a `client` that needs to read the same `NetDir` it feeds (like
+    /// [`netclient::TorDirClient`]) observes our updates instead of a disconnected copy, and takes
+    /// its wall-clock time and randomness from `clock` instead of always using
+    /// [`SystemDirClock`].
+    fn from_config_with_client_and_netdir(
+        config: NetDirConfig,
+        store: Box<dyn Store>,
+        client: Option<Arc<dyn bootstrap::DirClient>>,
+        netdir: SharedMutArc<NetDir>,
+        clock: Arc<dyn DirClock>,
+    ) -> Result<Self> {
+        let (status_tx, status_rx) = watch::channel();
         Ok(DirMgr {
             config,
             netdir,
+            status_tx,
+            status_rx,
+            store: Mutex::new(store),
+            client,
+            blockage: Mutex::new(None),
+            next_reset: Mutex::new(None),
+            clock,
         })
     }
 
+    /// Return a stream of [`DirBootstrapStatus`] updates, for callers that want to report
+    /// bootstrap progress instead of only finding out once (or if) it completes.
+    pub fn bootstrap_events(&self) -> impl Stream<Item = DirBootstrapStatus> {
+        self.status_rx.clone()
+    }
+
+    /// Return the reason bootstrap is stuck, if the last [`DirMgr::load_directory`] call hit one.
+    ///
+    /// This lets a caller ask "is this load stuck, and on what documents" directly, instead of
+    /// only finding out by inspecting an [`Error::Stalled`] returned from
+    /// [`DirMgr::load_once`]/[`DirMgr::bootstrap_from_config`].
+    pub fn bootstrap_blockage(&self) -> Option<DirBlockage> {
+        self.blockage.lock().expect("blockage lock poisoned").clone()
+    }
+
     /// Load the latest non-pending non-expired directory from the
     /// cache, if it is newer than the one we have.
     ///
-    /// Return false if there is no such consensus.
+    /// Return false if there is no such consensus. If we have a download client configured, a
+    /// consensus (or certificates, or microdescriptors) missing from the cache is fetched from
+    /// the network instead of leaving the load stuck. A state whose `reset_time()` has already
+    /// passed is reset and retried rather than advanced, so a stale cached consensus can't produce
+    /// an already-expired directory. If bootstrap instead gives up with [`Error::Stalled`], that
+    /// reason is recorded so [`DirMgr::bootstrap_blockage`] can report it.
     async fn load_directory(self: &Arc<Self>, docdir: &str) -> Result<bool> {
-        let state = state::GetConsensusState::new(Arc::downgrade(self), CacheUsage::CacheOnly)?;
-        let _ = bootstrap::load(Box::new(state), &docdir).await?;
+        let cache_usage = if self.client.is_some() {
+            CacheUsage::CacheOkay
+        } else {
+            CacheUsage::CacheOnly
+        };
+        let state =
+            state::GetConsensusState::new(Arc::downgrade(self), cache_usage, Arc::clone(&self.clock))?;
+        let reporter = StatusReporter {
+            status_tx: self.status_tx.clone(),
+            blockage: &self.blockage,
+        };
+
+        let loaded = bootstrap::load_with_policy(
+            Box::new(state),
+            &docdir,
+            self.client.as_deref(),
+            bootstrap::ExpiryPolicy::ResetOnExpiry,
+            Some(&reporter),
+        )
+        .await;
+
+        match loaded {
+            Ok(final_state) => {
+                *self.blockage.lock().expect("blockage lock poisoned") = None;
+                *self.next_reset.lock().expect("next_reset lock poisoned") = final_state.reset_time();
 
-        Ok(self.netdir.get().is_some())
+                let mut status_tx = self.status_tx.clone();
+                let _ = status_tx
+                    .send(DirBootstrapStatus::new(final_state.bootstrap_progress()))
+                    .await;
+
+                Ok(self.netdir.get().is_some())
+            }
+            Err(e) => {
+                if let Some(Error::Stalled {
+                    state_description,
+                    missing,
+                }) = e.downcast_ref::<Error>()
+                {
+                    let blockage = DirBlockage::new(format!(
+                        "stuck on {} with unresolvable missing documents: {:?}",
+                        state_description, missing
+                    ));
+                    *self.blockage.lock().expect("blockage lock poisoned") = Some(blockage);
+                }
+                Err(e)
+            }
+        }
     }
 
     /// Return an Arc handle to our latest directory, if we have one.
@@ -177,6 +470,81 @@ impl DirMgr {
     pub fn netdir(&self) -> Arc<NetDir> {
         self.opt_netdir().expect("DirMgr was not bootstrapped!")
     }
+
+    /// The reset time of our current directory state, if we have one, kept up to date by
+    /// [`DirMgr::load_directory`].
+    fn next_reset_time(&self) -> Option<SystemTime> {
+        *self.next_reset.lock().expect("next_reset lock poisoned")
+    }
+
+    /// Spawn a background task on `runtime` that keeps our cached directory fresh by re-running
+    /// [`DirMgr::load_directory`] against `docdir` every time our current state's `reset_time()`
+    /// comes due, without tearing this `DirMgr` down or losing whatever it has already
+    /// bootstrapped.
+    ///
+    /// The returned [`TaskHandle`] lets the embedding application suspend and resume this task on
+    /// demand -- for example, a mobile host can suspend it on entering the background and resume
+    /// it on returning to the foreground. Resuming fires a reload immediately, rather than waiting
+    /// out whatever delay was still pending when it was suspended, so a long suspension doesn't
+    /// also mean a long wait before the cache is checked again.
+    pub fn keep_directory_fresh<R: Runtime>(self: &Arc<Self>, runtime: R, docdir: String) -> TaskHandle {
+        let (mut schedule, handle) = TaskSchedule::new(runtime.clone());
+        // Run once right away, rather than waiting out a delay computed before we had ever loaded
+        // anything.
+        schedule.push_reset(None);
+
+        let dirmgr = Arc::clone(self);
+        if let Err(e) = runtime.spawn(async move {
+            while schedule.next().await.is_some() {
+                match dirmgr.load_directory(&docdir).await {
+                    Ok(changed) => info!("periodic directory reload: changed={}", changed),
+                    Err(e) => warn!("periodic directory reload failed: {}", e),
+                }
+
+                let delay = dirmgr
+                    .next_reset_time()
+                    .and_then(|reset_time| reset_time.duration_since(SystemTime::now()).ok());
+                schedule.push_reset(delay);
+            }
+        }) {
+            warn!("failed to spawn directory-refresh task: {}", e);
+        }
+
+        handle
+    }
+
+    /// Parse and count the relay identities named by a relay-churn update (`churn_text`, the same
+    /// one-hex-identity-per-line format as `churn.txt`), then trigger an early, full reconsensus
+    /// against `docdir` if it names any relay at all, instead of waiting for the current state's
+    /// `reset_time` to come due. Returns the number of distinct relay identities `churn_text`
+    /// named.
+    ///
+    /// # This is a full reconsensus, not an in-place patch
+    ///
+    /// Marking a departed relay unusable or splicing in a newly-listed one *in place* would need
+    /// a `tor_netdir::NetDir` API for editing relay membership -- nothing like that exists.
+    /// [`crate::shared_ref::SharedMutArc::mutate`] only lets us patch data about relays the
+    /// `NetDir` already has (that's how [`GetMicrodescsState::register_microdescs`] patches in
+    /// newly-downloaded microdescriptors), not add or remove the relays themselves. The only way
+    /// to change *which* relays are in a live `NetDir` is still
+    /// [`crate::shared_ref::SharedMutArc::replace`], driven by [`DirMgr::load_directory`]'s full
+    /// reconsensus -- so that's what this does. Treat this as "notice churn sooner," not as the
+    /// lightweight in-place update its name might suggest.
+    pub async fn apply_churn(self: &Arc<Self>, docdir: &str, churn_text: &str) -> Result<usize> {
+        let churned: std::collections::HashSet<_> =
+            churn::parse_churn(churn_text)?.into_iter().collect();
+        if churned.is_empty() {
+            return Ok(0);
+        }
+
+        info!(
+            "churn update names {} relay(s); running a full reconsensus early",
+            churned.len()
+        );
+        self.load_directory(docdir).await?;
+
+        Ok(churned.len())
+    }
 }
 
 /// A "state" object used to represent our progress in downloading a
@@ -213,6 +581,28 @@ trait DirState: Send {
     /// was any change in this state.
     fn add_from_cache(&mut self, docdir: &str) -> Result<bool>;
 
+    /// Add one or more documents fetched over the network by a [`bootstrap::DirClient`]; returns
+    /// 'true' if there was any change in this state. Mirrors [`DirState::add_from_cache`], except
+    /// the documents come as `(id, text)` pairs instead of a fixed on-disk path.
+    fn add_from_download(&mut self, results: &[(DocId, String)]) -> Result<bool>;
+
+    /// Return a snapshot of this state's bootstrap progress, for reporting to callers via
+    /// [`DirMgr::bootstrap_events`].
+    fn bootstrap_progress(&self) -> status::DirProgress;
+
+    /// Return true if the documents in [`DirState::missing_docs`] can never be resolved from the
+    /// cache, no matter how many more times this state is driven. States that can always make
+    /// progress (or that have nothing left missing) should leave this at its default of `false`.
+    fn missing_docs_unsatisfiable(&self) -> bool {
+        false
+    }
+
+    /// Return the retry policy [`crate::bootstrap::load`] should use while this state's
+    /// `missing_docs()` doesn't change between attempts.
+    fn retry_schedule(&self) -> retry::DownloadSchedule {
+        retry::DownloadSchedule::default()
+    }
+
     /// If possible, advance to the next state.
     fn advance(self: Box<Self>) -> Result<Box<dyn DirState>>;
     /// Return a time (if any) when downloaders should stop attempting to
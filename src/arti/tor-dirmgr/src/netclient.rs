@@ -0,0 +1,116 @@
+//! A [`bootstrap::DirClient`] that fetches missing directory documents from the Tor network.
+
+// Code mostly copied from Arti.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use log::warn;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tor_circmgr::CircMgr;
+use tor_netdir::NetDir;
+use tor_netdoc::doc::{authcert::AuthCertKeyIds, microdesc::MdDigest, netstatus::ConsensusFlavor};
+use tor_rtcompat::Runtime;
+
+use crate::bootstrap::DirClient;
+use crate::shared_ref::SharedMutArc;
+use crate::{DocId, Error, Result};
+
+/// A [`DirClient`] that downloads missing documents over a directory circuit built through a
+/// [`CircMgr`], picking a directory cache from whatever [`NetDir`] `netdir` currently holds.
+///
+/// # Limitations
+///
+/// Building a directory circuit needs *some* `NetDir` to pick a cache from, even a stale or
+/// expired one -- like [`crate::DirMgr::from_config_with_circuits`], this client keeps no list of
+/// fallback directories, so it cannot bootstrap a circuit from a completely empty cache. The very
+/// first run still needs a side-loaded consensus to get off the ground.
+pub(crate) struct TorDirClient<R: Runtime> {
+    /// How we build directory circuits.
+    circmgr: Arc<CircMgr<R>>,
+    /// The directory we pick a cache from; shared with the owning [`crate::DirMgr`] so that once
+    /// a download completes and produces a fresh `NetDir`, our next fetch uses it too.
+    netdir: SharedMutArc<NetDir>,
+}
+
+impl<R: Runtime> TorDirClient<R> {
+    /// Create a new client that builds directory circuits through `circmgr`, picking a cache from
+    /// whatever `netdir` currently holds.
+    pub(crate) fn new(circmgr: Arc<CircMgr<R>>, netdir: SharedMutArc<NetDir>) -> Self {
+        TorDirClient { circmgr, netdir }
+    }
+
+    /// Fetch `resource` (a directory-protocol path) from a directory cache over a new stream on a
+    /// directory circuit from `circmgr`, and return the response body as text.
+    async fn fetch(&self, netdir: &NetDir, resource: &str) -> Result<String> {
+        let circ = self.circmgr.get_or_launch_dir(netdir).await.map_err(|e| {
+            warn!("failed to build a directory circuit for {}: {}", resource, e);
+            Error::DirectoryNotPresent
+        })?;
+        let mut stream = circ.begin_dir_stream().await.map_err(|e| {
+            warn!("failed to open a directory stream for {}: {}", resource, e);
+            Error::DirectoryNotPresent
+        })?;
+
+        let request = format!("GET {} HTTP/1.0\r\nHost: dirserver\r\n\r\n", resource);
+        stream
+            .write_all(request.as_bytes())
+            .await
+            .map_err(|_| Error::DirectoryNotPresent)?;
+        stream.flush().await.map_err(|_| Error::DirectoryNotPresent)?;
+
+        let mut raw_response = Vec::new();
+        stream
+            .read_to_end(&mut raw_response)
+            .await
+            .map_err(|_| Error::DirectoryNotPresent)?;
+
+        let response = String::from_utf8(raw_response)
+            .map_err(|_| Error::BadNetworkConfig("directory response was not valid utf-8"))?;
+
+        response
+            .split_once("\r\n\r\n")
+            .map(|(_, body)| body.to_string())
+            .ok_or(Error::BadNetworkConfig("directory response had no body"))
+    }
+
+    /// Build the directory-protocol path for `id`.
+    fn resource_for(id: &DocId) -> String {
+        match id {
+            DocId::LatestConsensus { flavor, .. } => match flavor {
+                ConsensusFlavor::Microdesc => {
+                    "/tor/status-vote/current/consensus-microdesc".to_string()
+                }
+                ConsensusFlavor::Ns => "/tor/status-vote/current/consensus".to_string(),
+            },
+            DocId::AuthCert(AuthCertKeyIds {
+                id_fingerprint,
+                sk_fingerprint,
+            }) => format!("/tor/keys/fp-sk/{}-{}", id_fingerprint, sk_fingerprint),
+            DocId::Microdesc(digest) => format!("/tor/micro/d/{}", hex_encode(digest)),
+        }
+    }
+}
+
+/// Hex-encode `bytes`, lowercase, with no separators.
+fn hex_encode(bytes: &MdDigest) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[async_trait]
+impl<R: Runtime> DirClient for TorDirClient<R> {
+    async fn fetch_batch(&self, ids: &[DocId]) -> Result<Vec<(DocId, String)>> {
+        let netdir = self.netdir.get().ok_or(Error::DirectoryNotPresent)?;
+        let mut results = Vec::with_capacity(ids.len());
+
+        for id in ids {
+            let resource = Self::resource_for(id);
+            match self.fetch(&netdir, &resource).await {
+                Ok(body) => results.push((*id, body)),
+                Err(e) => warn!("failed to fetch {}: {}", resource, e),
+            }
+        }
+
+        Ok(results)
+    }
+}
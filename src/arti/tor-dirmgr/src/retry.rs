@@ -0,0 +1,132 @@
+//! Retry and backoff policy for directory downloads.
+
+// Code mostly copied from Arti.
+
+use std::time::Duration;
+
+/// How many times, and how quickly, to retry a document that didn't finish downloading or
+/// loading on the first attempt.
+///
+/// The delay before attempt `n` (`n` starting at 1) is `initial_delay * 2^(n-1)`, capped at
+/// `max_delay`. Bounding the attempt count is what closes the `TODO SECURITY` gap in
+/// `GetCertsState`: a consensus that names certificates our cache will never contain used to make
+/// [`crate::bootstrap::load`] spin forever; now it gives up with [`crate::Error::Stalled`] after a
+/// fixed number of tries.
+#[derive(Clone, Copy, Debug)]
+pub struct DownloadSchedule {
+    /// How many attempts to make before giving up on the current missing-document set and
+    /// resetting.
+    attempts: u32,
+    /// Delay before attempt 1, doubled for each attempt after it; the first retry (attempt 2)
+    /// therefore waits twice this long.
+    initial_delay: Duration,
+    /// Upper bound on the delay between attempts, no matter how many attempts have passed.
+    max_delay: Duration,
+    /// How many documents to fetch concurrently per attempt.
+    parallelism: u32,
+}
+
+impl Default for DownloadSchedule {
+    fn default() -> Self {
+        DownloadSchedule {
+            attempts: 3,
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            parallelism: 1,
+        }
+    }
+}
+
+impl DownloadSchedule {
+    /// Build a new schedule from its parameters.
+    pub fn new(attempts: u32, initial_delay: Duration, max_delay: Duration, parallelism: u32) -> Self {
+        DownloadSchedule {
+            attempts,
+            initial_delay,
+            max_delay,
+            parallelism,
+        }
+    }
+
+    /// How many attempts this schedule allows before giving up and resetting.
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// How many documents a downloader following this schedule may fetch concurrently.
+    pub fn parallelism(&self) -> u32 {
+        self.parallelism.max(1)
+    }
+
+    /// The delay to wait before making the given 1-based `attempt`.
+    pub fn delay_before(&self, attempt: u32) -> Duration {
+        // Cap the exponent well below where `1u32 << exponent` could overflow.
+        let exponent = attempt.saturating_sub(1).min(16);
+        self.initial_delay
+            .checked_mul(1u32 << exponent)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+}
+
+/// Tracks how many attempts have been spent on a [`crate::DirState`]'s current
+/// `missing_docs()`, so [`crate::bootstrap::load`] knows when to stop retrying an unchanging --
+/// possibly unobtainable -- document set and reset instead.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct RetryTracker {
+    /// Attempts made so far towards the current missing-document set.
+    attempt: u32,
+}
+
+impl RetryTracker {
+    /// Record that another full pass over `missing_docs()` has begun; returns the 1-based attempt
+    /// number this pass represents.
+    pub(crate) fn begin_attempt(&mut self) -> u32 {
+        self.attempt += 1;
+        self.attempt
+    }
+
+    /// Reset the attempt counter, e.g. once the state has advanced or been reset.
+    pub(crate) fn reset(&mut self) {
+        self.attempt = 0;
+    }
+
+    /// Return true if `schedule`'s attempt budget has been used up.
+    pub(crate) fn exhausted(&self, schedule: &DownloadSchedule) -> bool {
+        self.attempt >= schedule.attempts()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_before_doubles_then_caps() {
+        let schedule =
+            DownloadSchedule::new(5, Duration::from_secs(1), Duration::from_secs(10), 1);
+        assert_eq!(schedule.delay_before(1), Duration::from_secs(1));
+        assert_eq!(schedule.delay_before(2), Duration::from_secs(2));
+        assert_eq!(schedule.delay_before(3), Duration::from_secs(4));
+        assert_eq!(schedule.delay_before(4), Duration::from_secs(8));
+        // Would be 16s uncapped; max_delay caps it at 10s.
+        assert_eq!(schedule.delay_before(5), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn retry_tracker_exhausts_after_the_schedules_attempt_budget() {
+        let schedule = DownloadSchedule::new(3, Duration::from_secs(1), Duration::from_secs(1), 1);
+        let mut tracker = RetryTracker::default();
+
+        assert!(!tracker.exhausted(&schedule));
+        assert_eq!(tracker.begin_attempt(), 1);
+        assert!(!tracker.exhausted(&schedule));
+        assert_eq!(tracker.begin_attempt(), 2);
+        assert!(!tracker.exhausted(&schedule));
+        assert_eq!(tracker.begin_attempt(), 3);
+        assert!(tracker.exhausted(&schedule));
+
+        tracker.reset();
+        assert!(!tracker.exhausted(&schedule));
+    }
+}
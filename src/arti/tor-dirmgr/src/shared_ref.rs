@@ -0,0 +1,56 @@
+//! A shareable, mutable `Arc<T>` slot, for handles to the live [`tor_netdir::NetDir`] that
+//! multiple states and clients need to read a consistent snapshot of while only the directory
+//! manager itself ever replaces or patches it.
+
+// Code mostly copied from Arti.
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+
+/// A lock-protected `Option<Arc<T>>` that can be replaced or mutated in place through a shared
+/// reference, so callers holding a clone of the inner `Arc` keep a consistent snapshot even while
+/// a newer one is swapped in.
+#[derive(Debug)]
+pub(crate) struct SharedMutArc<T> {
+    /// The current value, or `None` before anything has ever been set.
+    current: Mutex<Option<Arc<T>>>,
+}
+
+impl<T> SharedMutArc<T> {
+    /// Construct a new, empty `SharedMutArc`.
+    pub(crate) fn new() -> Self {
+        SharedMutArc {
+            current: Mutex::new(None),
+        }
+    }
+
+    /// Return a clone of the current value, or `None` if nothing has been set yet.
+    pub(crate) fn get(&self) -> Option<Arc<T>> {
+        self.current.lock().expect("SharedMutArc lock poisoned").clone()
+    }
+
+    /// Replace the current value with `new_val`, discarding whatever was there before.
+    pub(crate) fn replace(&self, new_val: T) {
+        *self.current.lock().expect("SharedMutArc lock poisoned") = Some(Arc::new(new_val));
+    }
+
+    /// If a value is currently set, clone it, apply `func` to the clone, and swap it in as the new
+    /// current value. Returns `true` if there was a value to mutate, `false` otherwise.
+    pub(crate) fn mutate<F>(&self, func: F) -> Result<bool>
+    where
+        T: Clone,
+        F: FnOnce(&mut T) -> Result<()>,
+    {
+        let mut guard = self.current.lock().expect("SharedMutArc lock poisoned");
+        match guard.as_ref() {
+            Some(old) => {
+                let mut new_val = (**old).clone();
+                func(&mut new_val)?;
+                *guard = Some(Arc::new(new_val));
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
@@ -12,23 +12,23 @@
 
 // Code mostly copied from Arti.
 
-use std::fs;
 use anyhow::Context;
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use log::info;
-use rand::Rng;
 use std::collections::HashSet;
 use std::fmt::Debug;
-use std::sync::Weak;
+use std::path::Path;
+use std::sync::{Arc, Mutex, Weak};
 use std::time::{Duration, SystemTime};
 use tor_netdir::{MdReceiver, NetDir, PartialNetDir};
 use tor_netdoc::doc::netstatus::Lifetime;
 
 use crate::{
     //authority::default_authorities,
-    docmeta::ConsensusMeta, shared_ref::SharedMutArc, CacheUsage,
-    DirState, DocId, Error, NetDirConfig, Result,
+    clock::DirClock, docmeta::ConsensusMeta, err::DocSource, retry::DownloadSchedule,
+    shared_ref::SharedMutArc, status::DirProgress, store::Store, CacheUsage, DirState, DocId,
+    Error, NetDirConfig, Result,
 };
 use tor_checkable::{ExternallySigned, SelfSigned, Timebound};
 use tor_llcrypto::pk::rsa::RsaIdentity;
@@ -58,6 +58,9 @@ pub(crate) trait WriteNetDir: 'static + Sync + Send {
 
     /// Return a reference where we can write or modify a NetDir.
     fn netdir(&self) -> &SharedMutArc<NetDir>;
+
+    /// Return the store we should read and write cached documents through.
+    fn store(&self) -> &Mutex<Box<dyn Store>>;
 }
 
 impl WriteNetDir for crate::DirMgr {
@@ -67,6 +70,9 @@ impl WriteNetDir for crate::DirMgr {
     fn netdir(&self) -> &SharedMutArc<NetDir> {
         &self.netdir
     }
+    fn store(&self) -> &Mutex<Box<dyn Store>> {
+        &self.store
+    }
 }
 
 /// Initial state: fetching or loading a consensus directory.
@@ -89,25 +95,39 @@ pub(crate) struct GetConsensusState<DM: WriteNetDir> {
     /// A weak reference to the directory manager that wants us to
     /// fetch this information.  When this references goes away, we exit.
     writedir: Weak<DM>,
+
+    /// Source of wall-clock time and randomness for this bootstrap attempt and every state it
+    /// advances into, so a caller can replace [`crate::SystemDirClock`] with a simulated one.
+    clock: Arc<dyn DirClock>,
+
+    /// Which consensus flavor to ask for, from [`NetDirConfig::consensus_flavor`].
+    flavor: ConsensusFlavor,
 }
 
 impl<DM: WriteNetDir> GetConsensusState<DM> {
     /// Create a new GetConsensusState from a weak reference to a
-    /// directory manager and a `cache_usage` flag.
-    pub(crate) fn new(writedir: Weak<DM>, cache_usage: CacheUsage) -> Result<Self> {
-        let authority_ids: Vec<_> = Weak::upgrade(&writedir)
-            .context(Error::ManagerDropped)?
-            .config()
+    /// directory manager, a `cache_usage` flag, and a `clock` to use for every validity check and
+    /// randomized timing decision this state (and its successors) makes.
+    pub(crate) fn new(
+        writedir: Weak<DM>,
+        cache_usage: CacheUsage,
+        clock: Arc<dyn DirClock>,
+    ) -> Result<Self> {
+        let config = Weak::upgrade(&writedir).context(Error::ManagerDropped)?.config().clone();
+        let authority_ids: Vec<_> = config
             .authorities()
             .iter()
             .map(|auth| *auth.v3ident())
             .collect();
+        let flavor = config.consensus_flavor();
 
         Ok(GetConsensusState {
             cache_usage,
             next: None,
             authority_ids,
             writedir,
+            clock,
+            flavor,
         })
     }
 }
@@ -130,21 +150,47 @@ impl<DM: WriteNetDir> DirState for GetConsensusState<DM> {
         if self.can_advance() {
             return Vec::new();
         }
-        let flavor = ConsensusFlavor::Microdesc;
         vec![DocId::LatestConsensus {
-            flavor,
+            flavor: self.flavor,
             cache_usage: self.cache_usage,
         }]
     }
     fn can_advance(&self) -> bool {
         self.next.is_some()
     }
+    fn bootstrap_progress(&self) -> DirProgress {
+        DirProgress::NoConsensus
+    }
+    fn retry_schedule(&self) -> DownloadSchedule {
+        Weak::upgrade(&self.writedir)
+            .map(|wd| wd.config().schedule().retry_consensus)
+            .unwrap_or_default()
+    }
     fn add_from_cache(&mut self, docdir: &str) -> Result<bool> {
-        // side-loaded data
-        let consensus_path = format!("{}/consensus.txt", docdir);
-        let consensus = fs::read_to_string(consensus_path).context("Failed to read the consensus.")?;
-        self.add_consensus_text(true, consensus.as_str())
-            .map(|meta| meta.is_some())
+        let docdir = Path::new(docdir);
+        let writedir = Weak::upgrade(&self.writedir).context(Error::ManagerDropped)?;
+        let consensus = writedir
+            .store()
+            .lock()
+            .expect("store lock poisoned")
+            .latest_consensus(docdir, self.flavor)
+            .map_err(|e| Error::from_netdoc(DocSource::Consensus(docdir.to_path_buf()), e))?;
+        match consensus {
+            Some(text) => self
+                .add_consensus_text(DocSource::Consensus(docdir.to_path_buf()), true, &text)
+                .map(|meta| meta.is_some()),
+            None => Ok(false),
+        }
+    }
+    fn add_from_download(&mut self, results: &[(DocId, String)]) -> Result<bool> {
+        for (id, text) in results {
+            if matches!(id, DocId::LatestConsensus { .. }) {
+                return self
+                    .add_consensus_text(DocSource::Download(format!("{:?}", id)), false, text)
+                    .map(|meta| meta.is_some());
+            }
+        }
+        Ok(false)
     }
     fn advance(self: Box<Self>) -> Result<Box<dyn DirState>> {
         Ok(match self.next {
@@ -166,13 +212,21 @@ impl<DM: WriteNetDir> GetConsensusState<DM> {
     /// correct, or if it is illformed.
     fn add_consensus_text(
         &mut self,
+        source: DocSource,
         from_cache: bool,
         text: &str,
     ) -> Result<Option<&ConsensusMeta>> {
+        if self.flavor != ConsensusFlavor::Microdesc {
+            // `MdConsensus` is the only consensus type this crate knows how to parse and turn
+            // into a `NetDir`; see the limitation documented on `NetDirConfig::consensus_flavor`.
+            return Err(Error::UnsupportedConsensusFlavor(self.flavor).into());
+        }
+
         // Try to parse it and get its metadata.
         let (consensus_meta, unvalidated) = {
-            let (signedval, remainder, parsed) = MdConsensus::parse(text)?;
-            if let Ok(timely) = parsed.check_valid_now() {
+            let (signedval, remainder, parsed) = MdConsensus::parse(text)
+                .map_err(|e| Error::from_netdoc(source.clone(), e))?;
+            if let Ok(timely) = parsed.check_valid_at(&self.clock.now()) {
                 let meta = ConsensusMeta::from_unvalidated(signedval, remainder, &timely);
                 (meta, timely)
             } else {
@@ -206,6 +260,8 @@ impl<DM: WriteNetDir> GetConsensusState<DM> {
             missing_certs: desired_certs,
             certs: Vec::new(),
             writedir: Weak::clone(&self.writedir),
+            attempted_cache_load: false,
+            clock: Arc::clone(&self.clock),
         });
 
         Ok(Some(&self.next.as_ref().unwrap().consensus_meta))
@@ -242,6 +298,14 @@ struct GetCertsState<DM: WriteNetDir> {
     certs: Vec<AuthCert>,
     /// Reference to our directory manager.
     writedir: Weak<DM>,
+    /// Whether we have tried loading certificates from the cache at least once. Since this
+    /// directory manager only ever reads a single, non-refreshing `certificate.txt`, any
+    /// certificate still missing after that first attempt will never appear: see
+    /// [`DirState::missing_docs_unsatisfiable`].
+    attempted_cache_load: bool,
+    /// Source of wall-clock time and randomness, carried over from the [`GetConsensusState`] that
+    /// produced this state.
+    clock: Arc<dyn DirClock>,
 }
 
 #[async_trait]
@@ -263,19 +327,65 @@ impl<DM: WriteNetDir> DirState for GetCertsState<DM> {
     fn can_advance(&self) -> bool {
         self.unvalidated.key_is_correct(&self.certs[..]).is_ok()
     }
+    fn bootstrap_progress(&self) -> DirProgress {
+        DirProgress::FetchingCerts {
+            have: self.certs.len(),
+            need: self.certs.len() + self.missing_certs.len(),
+        }
+    }
+    fn missing_docs_unsatisfiable(&self) -> bool {
+        self.attempted_cache_load && !self.missing_certs.is_empty()
+    }
+    fn retry_schedule(&self) -> DownloadSchedule {
+        Weak::upgrade(&self.writedir)
+            .map(|wd| wd.config().schedule().retry_certs)
+            .unwrap_or_default()
+    }
     fn add_from_cache(&mut self, docdir: &str) -> Result<bool> {
         let mut changed = false;
-        // side-loaded data
-        let certificate_path = format!("{}/certificate.txt", docdir);
-        let certificate = fs::read_to_string(certificate_path).context("Failed to read the certificate.")?;
-        let parsed = AuthCert::parse(certificate.as_str())?.check_signature()?;
-        if let Ok(cert) = parsed.check_valid_now() {
+        self.attempted_cache_load = true;
+        let docdir = Path::new(docdir);
+        let writedir = Weak::upgrade(&self.writedir).context(Error::ManagerDropped)?;
+        let certificate = writedir
+            .store()
+            .lock()
+            .expect("store lock poisoned")
+            .authcerts(docdir)
+            .map_err(|e| Error::from_netdoc(DocSource::Certificate(docdir.to_path_buf()), e))?;
+        let certificate = match certificate {
+            Some(certificate) => certificate,
+            None => return Ok(false),
+        };
+        let parsed = AuthCert::parse(certificate.as_str())
+            .map_err(|e| Error::from_netdoc(DocSource::Certificate(docdir.to_path_buf()), e))?
+            .check_signature()
+            .map_err(|e| Error::from_netdoc(DocSource::Certificate(docdir.to_path_buf()), e))?;
+        if let Ok(cert) = parsed.check_valid_at(&self.clock.now()) {
             self.missing_certs.remove(cert.key_ids());
             self.certs.push(cert);
             changed = true;
         }
         Ok(changed)
     }
+    fn add_from_download(&mut self, results: &[(DocId, String)]) -> Result<bool> {
+        let mut changed = false;
+        for (id, text) in results {
+            if !matches!(id, DocId::AuthCert(_)) {
+                continue;
+            }
+            let source = DocSource::Download(format!("{:?}", id));
+            let parsed = AuthCert::parse(text.as_str())
+                .map_err(|e| Error::from_netdoc(source.clone(), e))?
+                .check_signature()
+                .map_err(|e| Error::from_netdoc(source, e))?;
+            if let Ok(cert) = parsed.check_valid_at(&self.clock.now()) {
+                self.missing_certs.remove(cert.key_ids());
+                self.certs.push(cert);
+                changed = true;
+            }
+        }
+        Ok(changed)
+    }
     fn advance(self: Box<Self>) -> Result<Box<dyn DirState>> {
         if self.can_advance() {
             let validated = self.unvalidated.check_signature(&self.certs[..])?;
@@ -283,6 +393,7 @@ impl<DM: WriteNetDir> DirState for GetCertsState<DM> {
                 validated,
                 self.consensus_meta,
                 self.writedir,
+                self.clock,
             )?))
         } else {
             Ok(self)
@@ -295,6 +406,7 @@ impl<DM: WriteNetDir> DirState for GetCertsState<DM> {
         Ok(Box::new(GetConsensusState::new(
             self.writedir,
             self.cache_usage,
+            self.clock,
         ).context("Failed to create new GetConsensusState when resetting GetCertsState")?))
     }
 }
@@ -318,12 +430,29 @@ struct GetMicrodescsState<DM: WriteNetDir> {
     /// find a new one.  Since this is randomized, we only compute it
     /// once.
     reset_time: SystemTime,
+    /// The total number of microdescriptors this directory needs, fixed at construction time,
+    /// used together with `missing.len()` to report fetch progress.
+    total_descs: usize,
+    /// Whether we have tried loading microdescriptors from the cache at least once. Like
+    /// [`GetCertsState::attempted_cache_load`], this is what
+    /// [`DirState::missing_docs_unsatisfiable`] checks: once the cache has been read, any
+    /// microdescriptor still missing is reported as unsatisfiable, whether or not a network
+    /// client could still fetch it.
+    attempted_cache_load: bool,
+    /// Source of wall-clock time and randomness, carried over from the [`GetCertsState`] that
+    /// produced this state.
+    clock: Arc<dyn DirClock>,
 }
 
 impl<DM: WriteNetDir> GetMicrodescsState<DM> {
     /// Create a new [`GetMicroDescsState`] from a provided
     /// microdescriptor consensus.
-    fn new(consensus: MdConsensus, meta: ConsensusMeta, writedir: Weak<DM>) -> Result<Self> {
+    fn new(
+        consensus: MdConsensus,
+        meta: ConsensusMeta,
+        writedir: Weak<DM>,
+        clock: Arc<dyn DirClock>,
+    ) -> Result<Self> {
         let reset_time = consensus.lifetime().valid_until();
 
         let partial_dir = match Weak::upgrade(&writedir) {
@@ -338,7 +467,8 @@ impl<DM: WriteNetDir> GetMicrodescsState<DM> {
             None => return Err(Error::ManagerDropped.into()),
         };
 
-        let missing = partial_dir.missing_microdescs().map(Clone::clone).collect();
+        let missing: HashSet<_> = partial_dir.missing_microdescs().map(Clone::clone).collect();
+        let total_descs = missing.len();
         let mut result = GetMicrodescsState {
             missing,
             writedir,
@@ -346,6 +476,9 @@ impl<DM: WriteNetDir> GetMicrodescsState<DM> {
             meta,
             newly_listed: Vec::new(),
             reset_time,
+            total_descs,
+            attempted_cache_load: false,
+            clock,
         };
 
         result.consider_upgrade().context("considering upgrade")?;
@@ -383,7 +516,7 @@ impl<DM: WriteNetDir> GetMicrodescsState<DM> {
         if let Some(p) = self.partial.take() {
             match p.unwrap_if_sufficient() {
                 Ok(netdir) => {
-                    self.reset_time = pick_download_time(netdir.lifetime())
+                    self.reset_time = pick_download_time(netdir.lifetime(), self.clock.as_ref())
                         .context("picking download time")?;
                     if let Some(wd) = Weak::upgrade(&self.writedir) {
                         wd.netdir().replace(netdir);
@@ -411,12 +544,41 @@ impl<DM: WriteNetDir> DirState for GetMicrodescsState<DM> {
     fn can_advance(&self) -> bool {
         false
     }
+    fn bootstrap_progress(&self) -> DirProgress {
+        if self.partial.is_none() {
+            DirProgress::Complete
+        } else {
+            DirProgress::FetchingMicrodescs {
+                have: self.total_descs - self.missing.len(),
+                need: self.total_descs,
+            }
+        }
+    }
+    fn missing_docs_unsatisfiable(&self) -> bool {
+        self.attempted_cache_load && !self.missing.is_empty()
+    }
+    fn retry_schedule(&self) -> DownloadSchedule {
+        Weak::upgrade(&self.writedir)
+            .map(|wd| wd.config().schedule().retry_microdescs)
+            .unwrap_or_default()
+    }
     fn add_from_cache(&mut self, docdir: &str) -> Result<bool> {
-        // side-loaded data
-        let microdescriptors_path = format!("{}/microdescriptors.txt", docdir);
-        let microdescriptors =
-            fs::read_to_string(microdescriptors_path).context("Failed to read microdescriptors.")?;
+        self.attempted_cache_load = true;
+        let docdir = Path::new(docdir);
+        let writedir = Weak::upgrade(&self.writedir).context(Error::ManagerDropped)?;
+        let wanted: Vec<MdDigest> = self.missing.iter().copied().collect();
+        let microdescriptors = writedir
+            .store()
+            .lock()
+            .expect("store lock poisoned")
+            .microdescs_by_digest(docdir, &wanted)
+            .map_err(|e| Error::from_netdoc(DocSource::Microdescs(docdir.to_path_buf()), e))?;
+        let microdescriptors = match microdescriptors {
+            Some(microdescriptors) => microdescriptors,
+            None => return Ok(false),
+        };
 
+        let mut changed = false;
         let mut new_mds = Vec::new();
         for anno in MicrodescReader::new(
             microdescriptors.as_str(),
@@ -425,13 +587,59 @@ impl<DM: WriteNetDir> DirState for GetMicrodescsState<DM> {
         .flatten()
         {
             let md = anno.into_microdesc();
-            self.missing.remove(md.digest());
+            changed |= self.missing.remove(md.digest());
             new_mds.push(md);
         }
 
         self.newly_listed.clear();
         self.register_microdescs(new_mds).context("registering microdescs")?;
 
+        if !self.newly_listed.is_empty() {
+            writedir
+                .store()
+                .lock()
+                .expect("store lock poisoned")
+                .update_last_listed(docdir, &self.newly_listed, SystemTime::now())?;
+        }
+
+        Ok(changed)
+    }
+    fn add_from_download(&mut self, results: &[(DocId, String)]) -> Result<bool> {
+        let writedir = Weak::upgrade(&self.writedir).context(Error::ManagerDropped)?;
+        let mut new_mds = Vec::new();
+        for (id, text) in results {
+            if !matches!(id, DocId::Microdesc(_)) {
+                continue;
+            }
+            let source = DocSource::Download(format!("{:?}", id));
+            for anno in
+                MicrodescReader::new(text.as_str(), AllowAnnotations::AnnotationsNotAllowed)
+            {
+                let anno = anno.map_err(|e| Error::from_netdoc(source.clone(), e))?;
+                let md = anno.into_microdesc();
+                self.missing.remove(md.digest());
+                new_mds.push(md);
+            }
+        }
+
+        if new_mds.is_empty() {
+            return Ok(false);
+        }
+
+        self.newly_listed.clear();
+        self.register_microdescs(new_mds)
+            .context("registering microdescs")?;
+
+        if !self.newly_listed.is_empty() {
+            // Downloaded microdescriptors aren't tied to a cache directory, but `Store` still
+            // wants one; both implementations ignore it for this call.
+            writedir
+                .store()
+                .lock()
+                .expect("store lock poisoned")
+                .update_last_listed(Path::new(""), &self.newly_listed, SystemTime::now())?;
+        }
+
         Ok(true)
     }
     fn advance(self: Box<Self>) -> Result<Box<dyn DirState>> {
@@ -444,17 +652,17 @@ impl<DM: WriteNetDir> DirState for GetMicrodescsState<DM> {
         Ok(Box::new(GetConsensusState::new(
             self.writedir,
             CacheUsage::MustDownload,
+            self.clock,
         ).context("Failed to create new GetConsensusState when resetting GetMicrodescsState")?))
     }
 }
 
 /// Choose a random download time to replace a consensus whose lifetime
-/// is `lifetime`.
-fn pick_download_time(lifetime: &Lifetime) -> Result<SystemTime> {
+/// is `lifetime`, drawing the random component from `clock`.
+fn pick_download_time(lifetime: &Lifetime, clock: &dyn DirClock) -> Result<SystemTime> {
     let (lowbound, uncertainty) = client_download_range(lifetime)
         .context("getting download range")?;
-    let zero = Duration::new(0, 0);
-    let t = lowbound + rand::thread_rng().gen_range(zero..uncertainty);
+    let t = lowbound + clock.random_duration(uncertainty);
     info!("The current consensus is fresh until {}, and valid until {}. I've picked {} as the earliest time to replace it.",
           DateTime::<Utc>::from(lifetime.fresh_until()),
           DateTime::<Utc>::from(lifetime.valid_until()),
@@ -0,0 +1,171 @@
+//! Types for reporting directory bootstrap progress, and for detecting when it has stalled for
+//! good.
+//!
+//! Before this module existed, [`DirState`](crate::DirState) only exposed `describe()` (a
+//! human-readable string with no structure) and `missing_docs()` (a list with no notion of how
+//! much of it is "normal, still downloading" versus "never going to arrive"). Callers had no way
+//! to show real progress, and no way to tell a slow bootstrap from a stuck one.
+
+// Code mostly copied from Arti.
+
+use std::time::{Duration, SystemTime};
+
+/// Which phase of directory bootstrap a [`DirState`](crate::DirState) is in, along with however
+/// much of that phase's work is done.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DirProgress {
+    /// No consensus has been loaded yet.
+    NoConsensus,
+    /// We have a consensus and are waiting on authority certificates to validate it.
+    FetchingCerts {
+        /// Certificates loaded so far.
+        have: usize,
+        /// Certificates needed before the consensus can be validated.
+        need: usize,
+    },
+    /// We have a validated consensus and are waiting on microdescriptors.
+    FetchingMicrodescs {
+        /// Microdescriptors loaded so far.
+        have: usize,
+        /// Microdescriptors needed before the directory is usable.
+        need: usize,
+    },
+    /// We have a complete, usable directory.
+    Complete,
+}
+
+impl DirProgress {
+    /// Return the fraction (in `0.0..=1.0`) of this phase's work that is done.
+    pub fn fraction(&self) -> f32 {
+        match self {
+            DirProgress::NoConsensus => 0.0,
+            DirProgress::FetchingCerts { have, need } | DirProgress::FetchingMicrodescs { have, need } => {
+                if *need == 0 {
+                    1.0
+                } else {
+                    *have as f32 / *need as f32
+                }
+            }
+            DirProgress::Complete => 1.0,
+        }
+    }
+}
+
+impl std::fmt::Display for DirProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DirProgress::NoConsensus => write!(f, "no consensus yet"),
+            DirProgress::FetchingCerts { have, need } => {
+                write!(f, "fetching certificates ({}/{})", have, need)
+            }
+            DirProgress::FetchingMicrodescs { have, need } => {
+                write!(f, "fetching microdescriptors ({}/{})", have, need)
+            }
+            DirProgress::Complete => write!(f, "complete"),
+        }
+    }
+}
+
+/// A point-in-time snapshot of bootstrap progress, broadcast to anyone watching
+/// [`DirMgr::bootstrap_events`](crate::DirMgr::bootstrap_events).
+#[derive(Clone, Debug, PartialEq)]
+pub struct DirBootstrapStatus {
+    /// The current phase and how much of it is done.
+    pub progress: DirProgress,
+}
+
+impl DirBootstrapStatus {
+    /// Build a status snapshot for `progress`.
+    pub(crate) fn new(progress: DirProgress) -> Self {
+        DirBootstrapStatus { progress }
+    }
+}
+
+impl Default for DirBootstrapStatus {
+    fn default() -> Self {
+        DirBootstrapStatus::new(DirProgress::NoConsensus)
+    }
+}
+
+/// A reason that directory bootstrap cannot proceed, no matter how long a caller waits.
+///
+/// This is the structured version of the `TODO SECURITY` in `GetCertsState`: a consensus can
+/// name authority certificates that our cache will never contain, and without a way to detect
+/// that, a caller driving the bootstrap loop has no choice but to wait forever.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DirBlockage(String);
+
+impl std::fmt::Display for DirBlockage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bootstrap cannot proceed: {}", self.0)
+    }
+}
+
+impl std::error::Error for DirBlockage {}
+
+impl DirBlockage {
+    /// Check whether a state stuck at `progress` for `stalled_for` (with no change since its last
+    /// progress update) and reporting `unsatisfiable` missing documents should be considered
+    /// permanently blocked, given `timeout`.
+    ///
+    /// Returns `None` unless both conditions hold: the state hasn't changed within `timeout`,
+    /// *and* it has told us that what remains can never be resolved from the cache.
+    pub fn detect(
+        progress: &DirProgress,
+        stalled_for: Duration,
+        timeout: Duration,
+        unsatisfiable: bool,
+    ) -> Option<DirBlockage> {
+        if !unsatisfiable || stalled_for < timeout {
+            return None;
+        }
+        Some(DirBlockage(format!(
+            "stuck at \"{}\" for over {:?} with unresolvable missing documents",
+            progress, timeout
+        )))
+    }
+
+    /// Build a blockage report directly, for a caller that already knows bootstrap is stuck (e.g.
+    /// from an [`Error::Stalled`](crate::Error::Stalled)) rather than needing to derive it from a
+    /// [`ProgressClock`].
+    pub(crate) fn new(message: String) -> Self {
+        DirBlockage(message)
+    }
+}
+
+/// How long a state may go without progress before its unresolvable missing documents are
+/// reported as a [`DirBlockage`] rather than silently retried forever.
+pub const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// A timestamped record of the last time bootstrap progress actually changed, used to measure
+/// how long a state has been stalled.
+#[derive(Clone, Debug)]
+pub(crate) struct ProgressClock {
+    /// The progress we last observed.
+    last_progress: DirProgress,
+    /// When we last observed a change in progress.
+    changed_at: SystemTime,
+}
+
+impl ProgressClock {
+    /// Start a clock at `now`, having observed `initial`.
+    pub(crate) fn new(initial: DirProgress, now: SystemTime) -> Self {
+        ProgressClock {
+            last_progress: initial,
+            changed_at: now,
+        }
+    }
+
+    /// Record an observation of `progress` at `now`, resetting the stall timer if it changed.
+    pub(crate) fn observe(&mut self, progress: DirProgress, now: SystemTime) {
+        if progress != self.last_progress {
+            self.last_progress = progress;
+            self.changed_at = now;
+        }
+    }
+
+    /// How long it has been since progress last changed, as of `now`.
+    pub(crate) fn stalled_for(&self, now: SystemTime) -> Duration {
+        now.duration_since(self.changed_at).unwrap_or_default()
+    }
+}
@@ -0,0 +1,208 @@
+//! Transparent response body decompression for `Content-Encoding: gzip`/`deflate`.
+//!
+//! Mirrors the identity/zlib decompressor Tor itself uses for directory connections: a small
+//! trait driven by repeatedly feeding it input and growing the output buffer, rather than
+//! assuming the whole compressed body fits any one fixed-size buffer.
+
+use anyhow::{bail, Context, Result};
+
+/// What happened during one [`Decompressor::process`] call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    /// Some output may have been produced; more input or output space may still be needed.
+    Written,
+    /// `out` filled up before all of `inp` could be processed; call again with more space.
+    OutOfSpace,
+    /// The compressed stream has ended; any bytes left over in `inp` are not part of it.
+    Done,
+}
+
+/// The result of one [`Decompressor::process`] call.
+pub struct Progress {
+    /// What the caller should do next.
+    pub status: Status,
+    /// How many bytes of `inp` this call consumed.
+    pub consumed: usize,
+    /// How many bytes this call wrote into `out`.
+    pub written: usize,
+}
+
+/// Something that can incrementally inflate a compressed response body.
+pub trait Decompressor {
+    /// Feed `inp` into the decompressor, writing as much decompressed output as fits into `out`.
+    fn process(&mut self, inp: &[u8], out: &mut [u8]) -> Result<Progress>;
+}
+
+/// Passes bytes through unchanged; used for an `identity` (or absent) `Content-Encoding`.
+#[derive(Default)]
+struct Identity;
+
+impl Decompressor for Identity {
+    fn process(&mut self, inp: &[u8], out: &mut [u8]) -> Result<Progress> {
+        let n = inp.len().min(out.len());
+        out[..n].copy_from_slice(&inp[..n]);
+        Ok(Progress {
+            status: if n < inp.len() {
+                Status::OutOfSpace
+            } else {
+                Status::Done
+            },
+            consumed: n,
+            written: n,
+        })
+    }
+}
+
+/// `deflate` (RFC1950 zlib framing) or the raw DEFLATE payload inside a `gzip` stream (RFC1951),
+/// backed by `flate2`'s raw streaming inflater.
+struct Zlib {
+    inner: flate2::Decompress,
+}
+
+impl Zlib {
+    /// `zlib_header` is `true` for `deflate` (has a zlib header/trailer) and `false` for the raw
+    /// DEFLATE payload inside a `gzip` stream, whose own header/trailer [`Gzip`] handles instead.
+    fn new(zlib_header: bool) -> Self {
+        Zlib {
+            inner: flate2::Decompress::new(zlib_header),
+        }
+    }
+}
+
+impl Decompressor for Zlib {
+    fn process(&mut self, inp: &[u8], out: &mut [u8]) -> Result<Progress> {
+        let before_in = self.inner.total_in();
+        let before_out = self.inner.total_out();
+        let status = self
+            .inner
+            .decompress(inp, out, flate2::FlushDecompress::None)
+            .context("inflate")?;
+        Ok(Progress {
+            status: match status {
+                flate2::Status::StreamEnd => Status::Done,
+                flate2::Status::BufError => Status::OutOfSpace,
+                flate2::Status::Ok => Status::Written,
+            },
+            consumed: (self.inner.total_in() - before_in) as usize,
+            written: (self.inner.total_out() - before_out) as usize,
+        })
+    }
+}
+
+/// `gzip`: a fixed/optional header, a raw DEFLATE stream, then a CRC32/size trailer. Skips the
+/// header on the first call and hands the rest to a raw (headerless) [`Zlib`] inflater; the
+/// trailer is simply left unconsumed once that inflater reports [`Status::Done`].
+struct Gzip {
+    header_skipped: bool,
+    inflate: Zlib,
+}
+
+impl Gzip {
+    fn new() -> Self {
+        Gzip {
+            header_skipped: false,
+            inflate: Zlib::new(false),
+        }
+    }
+}
+
+impl Decompressor for Gzip {
+    fn process(&mut self, inp: &[u8], out: &mut [u8]) -> Result<Progress> {
+        if self.header_skipped {
+            return self.inflate.process(inp, out);
+        }
+
+        let header_len = gzip_header_len(inp).context("parse gzip header")?;
+        self.header_skipped = true;
+        let mut progress = self.inflate.process(&inp[header_len..], out)?;
+        progress.consumed += header_len;
+        Ok(progress)
+    }
+}
+
+/// Parse a gzip member's fixed (10-byte) and optional (`FEXTRA`/`FNAME`/`FCOMMENT`/`FHCRC`)
+/// header, returning its total length in bytes.
+fn gzip_header_len(data: &[u8]) -> Result<usize> {
+    const MAGIC: [u8; 2] = [0x1f, 0x8b];
+    const FEXTRA: u8 = 0x04;
+    const FNAME: u8 = 0x08;
+    const FCOMMENT: u8 = 0x10;
+    const FHCRC: u8 = 0x02;
+
+    if data.len() < 10 || data[0..2] != MAGIC {
+        bail!("not a gzip stream");
+    }
+    let flags = data[3];
+    let mut pos = 10;
+
+    if flags & FEXTRA != 0 {
+        if data.len() < pos + 2 {
+            bail!("truncated gzip header (FEXTRA length)");
+        }
+        let xlen = u16::from_le_bytes([data[pos], data[pos + 1]]) as usize;
+        pos += 2 + xlen;
+    }
+    if flags & FNAME != 0 {
+        pos += find_nul(data.get(pos..).context("truncated gzip header (FNAME)")?)
+            .context("truncated gzip header (FNAME)")?
+            + 1;
+    }
+    if flags & FCOMMENT != 0 {
+        pos += find_nul(data.get(pos..).context("truncated gzip header (FCOMMENT)")?)
+            .context("truncated gzip header (FCOMMENT)")?
+            + 1;
+    }
+    if flags & FHCRC != 0 {
+        pos += 2;
+    }
+
+    if data.len() < pos {
+        bail!("truncated gzip header");
+    }
+    Ok(pos)
+}
+
+/// Find the first `0x00` byte in `data`.
+fn find_nul(data: &[u8]) -> Option<usize> {
+    data.iter().position(|&b| b == 0)
+}
+
+/// Build the right [`Decompressor`] for a `Content-Encoding` header value, or `None` if it names
+/// an encoding this client doesn't know how to undo.
+pub fn for_content_encoding(encoding: &str) -> Option<Box<dyn Decompressor + Send>> {
+    match encoding.trim() {
+        "" | "identity" => Some(Box::new(Identity) as Box<dyn Decompressor + Send>),
+        "deflate" => Some(Box::new(Zlib::new(true))),
+        "gzip" => Some(Box::new(Gzip::new())),
+        _ => None,
+    }
+}
+
+/// Run `decompressor` over all of `input`, growing the output buffer as needed, and return the
+/// fully decompressed bytes.
+pub fn decompress_all(mut decompressor: Box<dyn Decompressor + Send>, input: &[u8]) -> Result<Vec<u8>> {
+    let mut out = vec![0u8; (input.len() * 4).max(4096)];
+    let mut result = Vec::new();
+    let mut offset = 0;
+
+    loop {
+        let progress = decompressor.process(&input[offset..], &mut out)?;
+        result.extend_from_slice(&out[..progress.written]);
+        offset += progress.consumed;
+
+        match progress.status {
+            Status::Done => break,
+            Status::OutOfSpace => {
+                let new_len = out.len() * 2;
+                out.resize(new_len, 0);
+            }
+            Status::Written => {
+                if offset >= input.len() {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
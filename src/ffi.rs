@@ -3,7 +3,11 @@ mod android;
 #[cfg(target_os = "ios")]
 mod ios;
 
+#[cfg(any(target_os = "android", target_os = "ios"))]
+mod error;
 #[cfg(any(target_os = "android", target_os = "ios"))]
 mod structs;
 #[cfg(any(target_os = "android", target_os = "ios"))]
+pub(self) use error::FfiError;
+#[cfg(any(target_os = "android", target_os = "ios"))]
 pub(self) use structs::{Request, Response, RuntimeAndClient};
@@ -1,19 +1,39 @@
-use std::{mem::ManuallyDrop, ops::Deref, path::Path, ptr};
+use std::{
+    collections::HashMap,
+    mem::ManuallyDrop,
+    ops::Deref,
+    path::Path,
+    ptr,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
 
 use anyhow::{Context, Result};
+use futures::StreamExt;
 use jni::{
-    objects::{JClass, JObject, JString},
-    sys::{jbyteArray, jlong, jobject},
-    JNIEnv,
+    objects::{GlobalRef, JClass, JObject, JString, JThrowable, JValue},
+    signature::{JavaType, Primitive},
+    sys::{jbyteArray, jint, jlong, jobject},
+    JNIEnv, JavaVM,
 };
-use tracing::{info, log::Level};
+use tracing::{info, log::Level, warn};
+
+use crate::client::{BridgeConfig, ProxyConfig};
+use crate::Client;
 
-use super::{Request, Response, RuntimeAndClient};
+use super::{FfiError, Request, Response, RuntimeAndClient};
 
 mod conv;
+mod marshal;
+
+use marshal::FromJava;
 
 const ANDROID_LOG_TAG: &str = "ArtiLib";
-const TOR_LIB_EXCEPTION: &str = "org/c4dt/artiwrapper/TorLibException";
+const ARTI_EXCEPTION_CLASS: &str = "org/c4dt/artiwrapper/ArtiException";
+
+/// How long bootstrap progress can sit unchanged before we tell the Java callback it looks
+/// stalled, mirroring the stall timeout the directory manager itself uses internally.
+const BOOTSTRAP_STALL_TIMEOUT: Duration = Duration::from_secs(5 * 60);
 
 /// Minimal entry point used for testing purposes
 #[no_mangle]
@@ -77,6 +97,56 @@ pub unsafe extern "system" fn Java_org_c4dt_artiwrapper_Client_create(
     })
 }
 
+/// Create a Client that reaches the Tor network through a bridge and/or upstream HTTP(S) proxy.
+/// Any of `bridge_line_j`/`proxy_url_j`/`proxy_user_j`/`proxy_pass_j` may be Java `null` to leave
+/// that setting unused.
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_c4dt_artiwrapper_Client_createWithBridge(
+    env: JNIEnv,
+    _: JClass,
+    cache_dir_j: JString,
+    bridge_line_j: JString,
+    proxy_url_j: JString,
+    proxy_user_j: JString,
+    proxy_pass_j: JString,
+) -> jlong {
+    throw_on_err(env, 0, || {
+        let cache_dir_javastr = env
+            .get_string(cache_dir_j)
+            .context("create rust string for `cache_dir_j`")?;
+        let cache_dir = cache_dir_javastr
+            .deref()
+            .to_str()
+            .context("rust string from java")
+            .map(Path::new)?;
+
+        let bridge = optional_string(env, bridge_line_j)?.map(|line| BridgeConfig { line });
+        let proxy = optional_string(env, proxy_url_j)?.map(|url| ProxyConfig {
+            url,
+            username: optional_string(env, proxy_user_j).unwrap_or(None),
+            password: optional_string(env, proxy_pass_j).unwrap_or(None),
+        });
+
+        RuntimeAndClient::new_with_bridge(
+            cache_dir,
+            crate::client::DIRECTORY_CACHE_C4DT,
+            crate::client::DIRECTORY_CHURN_C4DT,
+            bridge,
+            proxy,
+        )
+        .context("create runtime and client")
+        .map(Into::into)
+    })
+}
+
+/// Convert a possibly-`null` Java string argument into `Option<String>`.
+fn optional_string(env: JNIEnv, value: JString) -> Result<Option<String>> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    String::from_java(env, value).map(Some)
+}
+
 /// Send a request with the given Client
 #[no_mangle]
 pub unsafe extern "system" fn Java_org_c4dt_artiwrapper_Client_send(
@@ -106,6 +176,279 @@ pub unsafe extern "system" fn Java_org_c4dt_artiwrapper_Client_send(
     })
 }
 
+/// Send a request with the given Client, honoring an explicit HTTP version (`"1.0"`/`"1.1"`,
+/// Java `null` defaulting to `"1.0"`) instead of always sending HTTP/1.0.
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_c4dt_artiwrapper_Client_sendWithVersion(
+    env: JNIEnv,
+    _: JClass,
+    java_client: jlong,
+    method_j: JString,
+    url_j: JString,
+    headers_j: JObject,
+    body_j: jbyteArray,
+    version_j: JString,
+) -> jobject {
+    throw_on_err(env, ptr::null_mut(), || {
+        let rt_and_client = RuntimeAndClient::from(java_client);
+        let request = Request::from_java_with_version(
+            env,
+            method_j,
+            url_j,
+            headers_j,
+            body_j,
+            Some(version_j),
+        )
+        .context("request from java")?;
+
+        let response = rt_and_client
+            .runtime()
+            .block_on(async { rt_and_client.client().send(request.0).await })
+            .context("send request")
+            .map(Response)?;
+
+        response
+            .into_java(env)
+            .context("response to java")
+            .map(JObject::into_inner)
+    })
+}
+
+/// Send a request with the given Client, streaming the response body to `callback` instead of
+/// buffering it into one byte array. `callback` must implement
+/// `org.c4dt.artiwrapper.StreamCallback`, with methods `onChunk([B)V` (called once per chunk) and
+/// `onComplete(ILjava/util/Map;)V` (called once, with the HTTP status code and the response
+/// headers as a `Map<String, List<String>>`, after the last chunk) -- so a streaming caller can
+/// read `Content-Type`/`ETag`/etc. the same way a buffered `Client_send` caller does, instead of
+/// only ever seeing the status code.
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_c4dt_artiwrapper_Client_sendStreaming(
+    env: JNIEnv,
+    _: JClass,
+    java_client: jlong,
+    method_j: JString,
+    url_j: JString,
+    headers_j: JObject,
+    body_j: jbyteArray,
+    callback: JObject,
+) {
+    throw_on_err(env, (), || {
+        let rt_and_client = RuntimeAndClient::from(java_client);
+        let request = Request::from_java(env, method_j, url_j, headers_j, body_j)
+            .context("request from java")?;
+
+        let callback_class = env
+            .get_object_class(callback)
+            .context("get callback class")?;
+        let on_chunk_method = env
+            .get_method_id(callback_class, "onChunk", "([B)V")
+            .context("find onChunk method")?;
+        let on_complete_method = env
+            .get_method_id(
+                callback_class,
+                "onComplete",
+                "(ILjava/util/Map;)V",
+            )
+            .context("find onComplete method")?;
+
+        let response = rt_and_client
+            .runtime()
+            .block_on(async {
+                rt_and_client
+                    .client()
+                    .send_streaming(request.0, |chunk| {
+                        let array: JObject = env
+                            .byte_array_from_slice(chunk)
+                            .context("create byte array")?
+                            .into();
+                        env.call_method_unchecked(
+                            callback,
+                            on_chunk_method,
+                            JavaType::Primitive(Primitive::Void),
+                            &[JValue::Object(array)],
+                        )
+                        .context("call onChunk")?;
+                        Ok(())
+                    })
+                    .await
+            })
+            .context("send streaming request")?;
+
+        let status: jint = response.status().as_u16().into();
+        let headers = marshal::header_map_into_java(
+            env,
+            response
+                .headers()
+                .iter()
+                .map(|(key, value)| -> Result<(String, String)> {
+                    Ok((
+                        key.to_string(),
+                        value
+                            .to_str()
+                            .context("convert header value to string")?
+                            .to_string(),
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()
+                .context("response headers")?
+                .into_iter(),
+        )
+        .context("build response headers")?;
+        env.call_method_unchecked(
+            callback,
+            on_complete_method,
+            JavaType::Primitive(Primitive::Void),
+            &[JValue::Int(status), JValue::Object(headers)],
+        )
+        .context("call onComplete")?;
+
+        Ok(())
+    })
+}
+
+/// Live bootstrap-progress watchers, keyed by the `jlong` client pointer they were registered
+/// against, so `Client_free` can tear one down before the `Client` it watches is freed.
+static BOOTSTRAP_WATCHERS: OnceLock<Mutex<HashMap<jlong, BootstrapWatcher>>> = OnceLock::new();
+
+/// A background task forwarding one `Client`'s bootstrap progress to a Java callback.
+///
+/// The task owns the callback's [`GlobalRef`] itself, so aborting it also releases that
+/// reference -- no need to track it separately here.
+struct BootstrapWatcher {
+    /// Aborts the forwarding task when dropped.
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for BootstrapWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Tracks whether [`BootstrapWatcher`]'s description has stopped changing for longer than
+/// [`BOOTSTRAP_STALL_TIMEOUT`], to report alongside `DirBootstrapStatus`'s own fields -- the
+/// status stream itself only ever reports forward progress, not the absence of it.
+struct StallWatch {
+    last_description: String,
+    changed_at: Instant,
+}
+
+impl StallWatch {
+    fn new() -> Self {
+        StallWatch {
+            last_description: String::new(),
+            changed_at: Instant::now(),
+        }
+    }
+
+    /// Record the latest description, and return a blockage kind if it has been unchanged for
+    /// too long.
+    fn observe(&mut self, description: &str) -> Option<&'static str> {
+        let now = Instant::now();
+        if description != self.last_description {
+            self.last_description = description.to_string();
+            self.changed_at = now;
+            return None;
+        }
+        (now.duration_since(self.changed_at) >= BOOTSTRAP_STALL_TIMEOUT).then_some("stalled")
+    }
+}
+
+/// Register a callback to be invoked on every directory bootstrap progress update for the given
+/// client, so the app can show a live "bootstrapping 40%..." indicator during the potentially
+/// slow initial directory load instead of waiting out a single blocking `create` call.
+///
+/// `callback` must implement `org.c4dt.artiwrapper.BootstrapCallback`, with a method
+/// `onBootstrapProgress(Ljava/lang/String;FLjava/lang/String;)V` taking a human-readable state
+/// description, the fraction complete (`0.0`-`1.0`), and a blockage kind (Java `null` unless
+/// progress looks stalled). Registering again for the same client replaces the previous callback.
+#[no_mangle]
+pub unsafe extern "system" fn Java_org_c4dt_artiwrapper_Client_registerBootstrapCallback(
+    env: JNIEnv,
+    _: JClass,
+    java_client: jlong,
+    callback: JObject,
+) {
+    throw_on_err(env, (), || {
+        let rt_and_client = RuntimeAndClient::from(java_client);
+        let vm = env.get_java_vm().context("get java vm")?;
+        let callback_ref = env.new_global_ref(callback).context("global ref callback")?;
+
+        // SAFETY: `java_client` stays valid (the `Client` it points to is never moved or freed)
+        // until `Client_free` runs, and `Client_free` always removes and drops this watcher --
+        // aborting its task -- before that happens, so this reference never outlives its target.
+        let client: &'static Client = &*(rt_and_client.client() as *const Client);
+        let task = rt_and_client
+            .runtime()
+            .spawn(forward_bootstrap_progress(client, vm, callback_ref));
+
+        let watchers = BOOTSTRAP_WATCHERS.get_or_init(|| Mutex::new(HashMap::new()));
+        watchers
+            .lock()
+            .expect("bootstrap watcher lock poisoned")
+            .insert(java_client, BootstrapWatcher { task });
+        Ok(())
+    })
+}
+
+/// Drive `client.bootstrap_events()` for as long as this task lives, forwarding each update to
+/// `callback` via `vm`.
+async fn forward_bootstrap_progress(client: &'static Client, vm: JavaVM, callback: GlobalRef) {
+    let mut events = Box::pin(client.bootstrap_events());
+    let mut stall = StallWatch::new();
+
+    // The client's background runtime is single-threaded, so this task always runs on the same
+    // OS thread for its whole life; attach it to the JVM once and let the guard detach it when
+    // the task ends (aborted or otherwise), rather than attaching/detaching per event.
+    let env = match vm.attach_current_thread() {
+        Ok(env) => env,
+        Err(e) => {
+            warn!("failed to attach bootstrap watcher thread to the JVM: {}", e);
+            return;
+        }
+    };
+
+    while let Some(status) = events.next().await {
+        let description = status.to_string();
+        let fraction = status.frac();
+        let blockage = stall.observe(&description);
+
+        if let Err(e) = call_bootstrap_callback(&env, &callback, &description, fraction, blockage)
+        {
+            warn!("failed to call bootstrap progress callback: {:#}", e);
+        }
+    }
+}
+
+/// Call the Java callback's `onBootstrapProgress` method with the given fields.
+fn call_bootstrap_callback(
+    env: &JNIEnv,
+    callback: &GlobalRef,
+    description: &str,
+    fraction: f32,
+    blockage: Option<&str>,
+) -> Result<()> {
+    let description_j = env.new_string(description).context("description string")?;
+    let blockage_j = match blockage {
+        Some(kind) => env.new_string(kind).context("blockage string")?.into(),
+        None => JObject::null(),
+    };
+
+    env.call_method(
+        callback.as_obj(),
+        "onBootstrapProgress",
+        "(Ljava/lang/String;FLjava/lang/String;)V",
+        &[
+            JValue::Object(description_j.into()),
+            JValue::Float(fraction),
+            JValue::Object(blockage_j),
+        ],
+    )
+    .context("call onBootstrapProgress")?;
+
+    Ok(())
+}
+
 /// Free the given Client
 #[no_mangle]
 pub unsafe extern "system" fn Java_org_c4dt_artiwrapper_Client_free(
@@ -113,12 +456,41 @@ pub unsafe extern "system" fn Java_org_c4dt_artiwrapper_Client_free(
     _: JClass,
     java_client: jlong,
 ) {
+    if let Some(watchers) = BOOTSTRAP_WATCHERS.get() {
+        watchers
+            .lock()
+            .expect("bootstrap watcher lock poisoned")
+            .remove(&java_client);
+    }
     ManuallyDrop::into_inner(RuntimeAndClient::from(java_client).0);
 }
 
 fn throw_on_err<T>(env: JNIEnv, default: T, act: impl FnOnce() -> Result<T>) -> T {
     act().unwrap_or_else(|e| {
-        let _ = env.throw((TOR_LIB_EXCEPTION, format!("{:#}", e)));
+        throw_arti_exception(env, &e);
         default
     })
 }
+
+/// Throw `org.c4dt.artiwrapper.ArtiException`, carrying both the classified
+/// [`FfiError`] code and the full error chain as its message, so callers can `catch` a single
+/// exception type and branch on `getCode()` instead of string-matching.
+fn throw_arti_exception(env: JNIEnv, err: &anyhow::Error) {
+    let code = FfiError::classify(err).code();
+    let message = format!("{:#}", err);
+
+    let exception = env.new_string(message.clone()).and_then(|msg| {
+        env.new_object(
+            ARTI_EXCEPTION_CLASS,
+            "(ILjava/lang/String;)V",
+            &[JValue::Int(code), JValue::Object(msg.into())],
+        )
+    });
+
+    let thrown = exception.and_then(|obj| env.throw(JThrowable::from(obj)));
+    if thrown.is_err() {
+        // The ArtiException(int, String) constructor may not exist on older client jars; fall
+        // back to a plain message-only exception rather than losing the error entirely.
+        let _ = env.throw_new(ARTI_EXCEPTION_CLASS, message);
+    }
+}
@@ -3,13 +3,13 @@ use std::mem::ManuallyDrop;
 use anyhow::{Context, Result};
 use http::{Uri, Version};
 use jni::{
-    objects::{JList, JMap, JObject, JString, JValue},
+    objects::{JObject, JString},
     sys::{jbyteArray, jint, jlong},
     JNIEnv,
 };
 use tokio::runtime::Runtime;
-use tracing::trace;
 
+use super::marshal::{header_map_from_java, header_map_into_java, FromJava, IntoJava};
 use super::{Request, Response, RuntimeAndClient};
 use crate::Client;
 
@@ -28,6 +28,7 @@ impl From<RuntimeAndClient> for jlong {
 }
 
 impl Request {
+    /// Build a request defaulting to HTTP/1.0, for callers not passing an explicit version.
     pub fn from_java(
         env: JNIEnv,
         method_j: JString,
@@ -35,48 +36,49 @@ impl Request {
         headers_j: JObject,
         body_j: jbyteArray,
     ) -> Result<Self> {
-        let method: String = env
-            .get_string(method_j)
-            .context("create rust string for `method_j`")?
-            .into();
-
-        let url: String = env
-            .get_string(url_j)
-            .context("create rust string for `url_j`")?
-            .into();
+        Self::from_java_with_version(env, method_j, url_j, headers_j, body_j, None)
+    }
 
-        let body: Vec<u8> = env
-            .convert_byte_array(body_j)
-            .context("create byte array")?;
+    /// Build a request, honoring an explicit `version_j` of `"1.0"`/`"1.1"` (Java `null` default
+    /// to HTTP/1.0). A `Host` header already present in `headers_j` is kept as-is rather than
+    /// overwritten, so HTTP/1.1 callers that already supply one don't end up with two.
+    pub fn from_java_with_version(
+        env: JNIEnv,
+        method_j: JString,
+        url_j: JString,
+        headers_j: JObject,
+        body_j: jbyteArray,
+        version_j: Option<JString>,
+    ) -> Result<Self> {
+        let method = String::from_java(env, method_j).context("method")?;
+        let url = String::from_java(env, url_j).context("url")?;
+        let body = Vec::<u8>::from_java(env, body_j).context("body")?;
+
+        let version = match version_j {
+            Some(v) if !v.is_null() => match String::from_java(env, v)?.as_str() {
+                "1.1" => Version::HTTP_11,
+                _ => Version::HTTP_10,
+            },
+            _ => Version::HTTP_10,
+        };
 
         let uri = url.parse::<Uri>().context("parse URL")?;
         let host = uri.host().unwrap_or("");
 
+        let headers = header_map_from_java(env, headers_j).context("request headers")?;
+        let has_host_header = headers.keys().any(|name| name.eq_ignore_ascii_case("host"));
+
         let mut req_builder = http::Request::builder()
             .method(method.as_bytes())
-            .header("Host", host)
             .uri(uri)
-            .version(Version::HTTP_10);
-
-        let headers_jmap: JMap = env.get_map(headers_j).context("create JMap")?;
-
-        for (key, value_list) in headers_jmap.iter().context("create JMap iterator")? {
-            let header_name: String = env
-                .get_string(JString::from(key))
-                .context("create rust string for header name")?
-                .into();
-            trace!("Request header_name: {:?}", header_name);
-
-            let header_value_list: JList = env.get_list(value_list).context("create JList")?;
-
-            for value in header_value_list.iter().context("create JList iterator")? {
-                let header_value: String = env
-                    .get_string(JString::from(value))
-                    .context("create rust string for header value")?
-                    .into();
-                trace!("    Request header_value: {:?}", header_value);
+            .version(version);
+        if !has_host_header {
+            req_builder = req_builder.header("Host", host);
+        }
 
-                req_builder = req_builder.header(header_name.as_str(), header_value);
+        for (name, values) in headers {
+            for value in values {
+                req_builder = req_builder.header(name.as_str(), value);
             }
         }
 
@@ -95,62 +97,27 @@ impl Response {
             .new_string(format!("{:?}", self.0.version()))
             .context("build http string version")?;
 
-        let headers = env
-            .new_object(
-                env.find_class("java/util/HashMap")
-                    .context("find java.util.HashMap")?,
-                "()V",
-                &[],
-            )
-            .context("create HashMap")?;
-
-        for (key, value) in self.0.headers() {
-            trace!("Response header: {:?} → {:?}", key, value);
-
-            let mut entry = env
-                .new_object(
-                    env.find_class("java/util/ArrayList")
-                        .context("find java.util.ArrayList")?,
-                    "()V",
-                    &[],
-                )
-                .context("create ArrayList")?;
-
-            if let JValue::Object(o) = env
-                .call_method(
-                    headers,
-                    "putIfAbsent",
-                    "(Ljava/lang/Object;Ljava/lang/Object;)Ljava/lang/Object;",
-                    &[
-                        env.new_string(key.as_str())
-                            .context("create JString for key")?
-                            .into(),
-                        entry.into(),
-                    ],
-                )
-                .context("call HashMap.put()")?
-            {
-                if !o.into_inner().is_null() {
-                    trace!("Entry already existed -- appending");
-                    entry = o;
-                }
-            }
-
-            env.call_method(
-                entry,
-                "add",
-                "(Ljava/lang/Object;)Z",
-                &[env
-                    .new_string(value.to_str().context("convert header value to string")?)
-                    .context("create JString for value")?
-                    .into()],
-            )
-            .context("call List.add()")?;
-        }
+        let headers = header_map_into_java(
+            env,
+            self.0
+                .headers()
+                .iter()
+                .map(|(key, value)| -> Result<(String, String)> {
+                    Ok((
+                        key.to_string(),
+                        value
+                            .to_str()
+                            .context("convert header value to string")?
+                            .to_string(),
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()
+                .context("response headers")?
+                .into_iter(),
+        )
+        .context("build response headers")?;
 
-        let body = env
-            .byte_array_from_slice(self.0.body())
-            .context("create byte array")?;
+        let body = self.0.body().clone().into_java(env).context("body")?;
 
         env.new_object(
             env.find_class("org/c4dt/artiwrapper/HttpResponse")
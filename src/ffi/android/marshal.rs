@@ -0,0 +1,161 @@
+//! Generic Java ↔ Rust value conversion for JNI.
+//!
+//! `Request::from_java`/`Response::into_java` used to hand-roll every field conversion: JString
+//! to String, header JMap iteration, JList/HashMap/ArrayList construction. This module factors
+//! that into a small trait pair so a new type only needs one `impl` instead of its own
+//! `find_class`/`new_object`/`call_method` chain.
+
+use std::collections::HashMap;
+
+use anyhow::{Context, Result};
+use jni::objects::{JList, JMap, JObject, JString, JValue};
+use jni::JNIEnv;
+
+/// Convert a JNI value into its Rust representation.
+pub trait FromJava<'a>: Sized {
+    /// The JNI type this is converted from.
+    type Java;
+    /// Perform the conversion, using `env` to call back into the JVM as needed.
+    fn from_java(env: JNIEnv<'a>, java: Self::Java) -> Result<Self>;
+}
+
+/// Convert a Rust value into its JNI representation.
+pub trait IntoJava<'a> {
+    /// Build the JNI value, using `env` to allocate JVM objects as needed.
+    fn into_java(self, env: JNIEnv<'a>) -> Result<JObject<'a>>;
+}
+
+impl<'a> FromJava<'a> for String {
+    type Java = JString<'a>;
+
+    fn from_java(env: JNIEnv<'a>, java: Self::Java) -> Result<Self> {
+        env.get_string(java)
+            .context("create rust string from JString")
+            .map(Into::into)
+    }
+}
+
+impl<'a> IntoJava<'a> for String {
+    fn into_java(self, env: JNIEnv<'a>) -> Result<JObject<'a>> {
+        env.new_string(self)
+            .context("create JString")
+            .map(Into::into)
+    }
+}
+
+impl<'a> FromJava<'a> for Vec<u8> {
+    type Java = jni::sys::jbyteArray;
+
+    fn from_java(env: JNIEnv<'a>, java: Self::Java) -> Result<Self> {
+        env.convert_byte_array(java).context("create byte array")
+    }
+}
+
+impl<'a> IntoJava<'a> for Vec<u8> {
+    fn into_java(self, env: JNIEnv<'a>) -> Result<JObject<'a>> {
+        env.byte_array_from_slice(&self)
+            .context("create byte array")
+            .map(Into::into)
+    }
+}
+
+/// Build a `java.util.ArrayList` containing each element's [`IntoJava`] conversion.
+impl<'a, T: IntoJava<'a>> IntoJava<'a> for Vec<T> {
+    fn into_java(self, env: JNIEnv<'a>) -> Result<JObject<'a>> {
+        let list = env
+            .new_object(
+                env.find_class("java/util/ArrayList")
+                    .context("find java.util.ArrayList")?,
+                "()V",
+                &[],
+            )
+            .context("create ArrayList")?;
+
+        for item in self {
+            let value = item.into_java(env)?;
+            env.call_method(
+                list,
+                "add",
+                "(Ljava/lang/Object;)Z",
+                &[JValue::Object(value)],
+            )
+            .context("call List.add()")?;
+        }
+
+        Ok(list)
+    }
+}
+
+/// Read a `Map<String, List<String>>` (as used for HTTP headers) into a Rust multimap.
+pub fn header_map_from_java<'a>(
+    env: JNIEnv<'a>,
+    headers_j: JObject<'a>,
+) -> Result<HashMap<String, Vec<String>>> {
+    let headers_jmap: JMap = env.get_map(headers_j).context("create JMap")?;
+
+    let mut headers = HashMap::new();
+    for (key, value_list) in headers_jmap.iter().context("create JMap iterator")? {
+        let name = String::from_java(env, JString::from(key)).context("header name")?;
+        let values_jlist: JList = env.get_list(value_list).context("create JList")?;
+
+        let mut values = Vec::new();
+        for value in values_jlist.iter().context("create JList iterator")? {
+            values.push(String::from_java(env, JString::from(value)).context("header value")?);
+        }
+
+        headers.entry(name).or_insert_with(Vec::new).extend(values);
+    }
+
+    Ok(headers)
+}
+
+/// Build a `Map<String, List<String>>` (as used for HTTP headers) from a Rust multimap,
+/// appending to an existing entry's list rather than overwriting it if the key repeats.
+pub fn header_map_into_java<'a>(
+    env: JNIEnv<'a>,
+    headers: impl Iterator<Item = (String, String)>,
+) -> Result<JObject<'a>> {
+    let map = env
+        .new_object(
+            env.find_class("java/util/HashMap")
+                .context("find java.util.HashMap")?,
+            "()V",
+            &[],
+        )
+        .context("create HashMap")?;
+
+    for (key, value) in headers {
+        let mut entry = env
+            .new_object(
+                env.find_class("java/util/ArrayList")
+                    .context("find java.util.ArrayList")?,
+                "()V",
+                &[],
+            )
+            .context("create ArrayList")?;
+
+        if let JValue::Object(o) = env
+            .call_method(
+                map,
+                "putIfAbsent",
+                "(Ljava/lang/Object;Ljava/lang/Object;)Ljava/lang/Object;",
+                &[key.clone().into_java(env)?.into(), entry.into()],
+            )
+            .context("call HashMap.put()")?
+        {
+            if !o.into_inner().is_null() {
+                entry = o;
+            }
+        }
+
+        env.call_method(
+            entry,
+            "add",
+            "(Ljava/lang/Object;)Z",
+            &[value.into_java(env)?.into()],
+        )
+        .context("call List.add()")?;
+    }
+
+    Ok(map)
+}
@@ -0,0 +1,61 @@
+//! Typed errors for the FFI boundary.
+//!
+//! Both the JNI and iOS entry points only ever had `anyhow::Result` internally, with no defined
+//! contract for what reaches Java/Swift: a parse failure, a Tor circuit failure, and a timeout
+//! were all indistinguishable opaque strings. [`FfiError`] classifies a failure into the handful
+//! of kinds mobile callers actually need to branch on.
+
+use anyhow::Error as AnyError;
+
+/// A classified FFI failure, with a stable integer code callers can match on without parsing the
+/// human-readable message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FfiError {
+    /// The request could not be built (bad method, URL, or headers).
+    InvalidRequest,
+    /// No usable Tor directory is available yet.
+    DirectoryUnavailable,
+    /// Building a circuit, or connecting over one, failed.
+    CircuitFailed,
+    /// The operation exceeded its deadline.
+    Timeout,
+    /// A local I/O error (e.g. cache directory access).
+    Io,
+    /// Anything that doesn't fit one of the above.
+    Other,
+}
+
+impl FfiError {
+    /// A stable, caller-matchable integer code: `InvalidRequest` through `Io` are `1` to `5` in
+    /// the order listed here, and `Other` is `0`.
+    pub fn code(self) -> i32 {
+        match self {
+            FfiError::InvalidRequest => 1,
+            FfiError::DirectoryUnavailable => 2,
+            FfiError::CircuitFailed => 3,
+            FfiError::Timeout => 4,
+            FfiError::Io => 5,
+            FfiError::Other => 0,
+        }
+    }
+
+    /// Classify an [`anyhow::Error`] produced by one of this crate's FFI entry points.
+    pub fn classify(err: &AnyError) -> Self {
+        if err.downcast_ref::<std::io::Error>().is_some() {
+            return FfiError::Io;
+        }
+        if err.downcast_ref::<tor_dirmgr::Error>().is_some() {
+            return FfiError::DirectoryUnavailable;
+        }
+        if err.downcast_ref::<arti_client::Error>().is_some() {
+            return FfiError::CircuitFailed;
+        }
+        if err.downcast_ref::<tokio::time::error::Elapsed>().is_some() {
+            return FfiError::Timeout;
+        }
+        if err.downcast_ref::<http::Error>().is_some() {
+            return FfiError::InvalidRequest;
+        }
+        FfiError::Other
+    }
+}
@@ -9,6 +9,8 @@ use core_foundation::{
 mod conv;
 mod structs;
 
+use crate::client::{BridgeConfig, ProxyConfig};
+
 use super::{Request, Response, RuntimeAndClient};
 
 #[no_mangle]
@@ -35,6 +37,52 @@ pub unsafe extern "C" fn client_new(cache_dir_ref: CFStringRef) -> structs::Resu
     .into()
 }
 
+/// Create a Client that reaches the Tor network through a bridge and/or upstream HTTP(S) proxy.
+/// Any of `bridge_line_ref`/`proxy_url_ref`/`proxy_user_ref`/`proxy_pass_ref` may be a null
+/// pointer to leave that setting unused.
+#[no_mangle]
+pub unsafe extern "C" fn client_new_with_bridge(
+    cache_dir_ref: CFStringRef,
+    bridge_line_ref: CFStringRef,
+    proxy_url_ref: CFStringRef,
+    proxy_user_ref: CFStringRef,
+    proxy_pass_ref: CFStringRef,
+) -> structs::Result<isize> {
+    {
+        let cache_dir_ios = CFString::wrap_under_get_rule(cache_dir_ref);
+        let cache_dir_raw: Cow<_> = (&cache_dir_ios).into();
+        let cache_dir = Path::new(cache_dir_raw.as_ref());
+
+        let bridge = optional_cf_string(bridge_line_ref).map(|line| BridgeConfig { line });
+        let proxy = optional_cf_string(proxy_url_ref).map(|url| ProxyConfig {
+            url,
+            username: optional_cf_string(proxy_user_ref),
+            password: optional_cf_string(proxy_pass_ref),
+        });
+
+        RuntimeAndClient::new_with_bridge(
+            cache_dir,
+            crate::client::DIRECTORY_CACHE_C4DT,
+            crate::client::DIRECTORY_CHURN_C4DT,
+            bridge,
+            proxy,
+        )
+        .context("create runtime and client")
+        .map(Into::into)
+    }
+    .into()
+}
+
+/// Convert a possibly-null `CFStringRef` argument into `Option<String>`.
+unsafe fn optional_cf_string(string_ref: CFStringRef) -> Option<String> {
+    if string_ref.is_null() {
+        return None;
+    }
+    let string = CFString::wrap_under_get_rule(string_ref);
+    let raw: Cow<_> = (&string).into();
+    Some(raw.into_owned())
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn client_send(
     ios_client: isize,
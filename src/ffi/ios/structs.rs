@@ -186,6 +186,9 @@ pub union ResultUnion<T> {
 pub struct Result<T> {
     /// Is `value.ok` field valid?
     is_ok: bool,
+    /// Classified [`super::super::FfiError`] code on failure (`0` when `is_ok`), so Swift callers
+    /// get an actionable, matchable status instead of only a human-readable message.
+    error_code: i32,
     /// Contained value
     value: ResultUnion<T>,
 }
@@ -193,6 +196,10 @@ pub struct Result<T> {
 impl<T> From<anyhow::Result<T>> for Result<T> {
     fn from(res: anyhow::Result<T>) -> Self {
         let is_ok = res.is_ok();
+        let error_code = match &res {
+            Ok(_) => 0,
+            Err(err) => super::super::FfiError::classify(err).code(),
+        };
 
         let value = match res {
             Ok(ok) => ResultUnion {
@@ -203,6 +210,10 @@ impl<T> From<anyhow::Result<T>> for Result<T> {
             },
         };
 
-        Self { is_ok, value }
+        Self {
+            is_ok,
+            error_code,
+            value,
+        }
     }
 }
@@ -5,7 +5,7 @@ use std::{mem::ManuallyDrop, path::Path};
 use anyhow::{Context, Result};
 use tokio::runtime::Runtime;
 
-use crate::client::{DIRECTORY_CACHE_C4DT, DIRECTORY_CHURN_C4DT};
+use crate::client::{BridgeConfig, ProxyConfig, DIRECTORY_CACHE_C4DT, DIRECTORY_CHURN_C4DT};
 use crate::Client;
 
 /// Wrap a [`Runtime`] and a [`Client`], useful for crossing FFI boundaries
@@ -22,6 +22,19 @@ impl RuntimeAndClient {
         cache_dir: &Path,
         directory_cache: &str,
         churn_cache: &str,
+    ) -> Result<Self> {
+        Self::new_with_bridge(cache_dir, directory_cache, churn_cache, None, None)
+    }
+
+    /// Create a new [`RuntimeAndClient`], additionally reaching the Tor network through the
+    /// given bridge and/or upstream HTTP(S) proxy. Needed by callers in censored networks where
+    /// a direct connection to a guard (or even to the bridge's own transport) is blocked.
+    pub fn new_with_bridge(
+        cache_dir: &Path,
+        directory_cache: &str,
+        churn_cache: &str,
+        bridge: Option<BridgeConfig>,
+        proxy: Option<ProxyConfig>,
     ) -> Result<Self> {
         let rt = tokio::runtime::Builder::new_current_thread()
             .enable_all()
@@ -29,7 +42,10 @@ impl RuntimeAndClient {
             .context("build tokio runtime")?;
 
         let client = rt
-            .block_on(async { Client::new_with_url(cache_dir, directory_cache, churn_cache).await })
+            .block_on(async {
+                Client::new_with_bridge(cache_dir, directory_cache, churn_cache, bridge, proxy)
+                    .await
+            })
             .context("create client")?;
 
         Ok(Self(ManuallyDrop::new(Box::new((rt, client)))))
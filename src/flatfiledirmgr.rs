@@ -2,10 +2,12 @@
 //! Used for 'lightarti'.
 
 use arti_client::DirProviderBuilder;
-use tor_checkable::{ExternallySigned, SelfSigned, TimeValidityError, Timebound};
+use tor_checkable::{ExternallySigned, SelfSigned, Timebound};
 use tor_circmgr::CircMgr;
 use tor_dirmgr::config::DirMgrConfig;
-use tor_dirmgr::{DirBootstrapStatus, DirMgrStore, DirProvider, Error, Result, SharedMutArc};
+use tor_dirmgr::{
+    DirBootstrapStatus, DirMgrStore, DirProgress, DirProvider, Error, Result, SharedMutArc,
+};
 use tor_llcrypto::pk::rsa::RsaIdentity;
 use tor_netdir::{DirEvent, MdReceiver, NetDir, NetDirProvider, PartialNetDir, Timeliness};
 use tor_netdoc::doc::authcert::AuthCert;
@@ -14,7 +16,7 @@ use tor_netdoc::doc::netstatus::{
     MdConsensus, MdConsensusRouterStatus, RouterStatus, UnvalidatedConsensus,
 };
 use tor_netdoc::AllowAnnotations;
-use tor_rtcompat::Runtime;
+use tor_rtcompat::{Runtime, SleepProvider};
 
 use async_trait::async_trait;
 use futures::stream::BoxStream;
@@ -23,14 +25,147 @@ use tracing::{debug, info, warn};
 
 use rand::seq::SliceRandom;
 use std::collections::HashSet;
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tor_netdir::params::NetParameters;
 
+use crate::http::{raw_to_response, request_to_raw};
+
 /// 1/CHURN_FRACTION is the threshold of the consensus relays that we can remove with the churn
 const CHURN_FRACTION: usize = 6;
 
+/// Identifies which flat cache file a load, parse, or validation failure came from.
+///
+/// Carrying this alongside the underlying cause lets us log (and, in the future, report to
+/// FFI/Android callers) *which* of the four cache files is broken instead of collapsing every
+/// failure into the same opaque [`Error::CacheCorruption`].
+#[derive(Debug, Clone)]
+enum DocSource {
+    /// The consensus document.
+    Consensus(PathBuf),
+    /// The authority certificate(s).
+    Certificate(PathBuf),
+    /// The microdescriptors.
+    Microdescriptors(PathBuf),
+    /// The churn file.
+    Churn(PathBuf),
+}
+
+impl fmt::Display for DocSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, path) = match self {
+            DocSource::Consensus(p) => ("consensus", p),
+            DocSource::Certificate(p) => ("certificate", p),
+            DocSource::Microdescriptors(p) => ("microdescriptors", p),
+            DocSource::Churn(p) => ("churn", p),
+        };
+        write!(f, "{} ({})", name, path.to_string_lossy())
+    }
+}
+
+/// Coarse classification of a [`DocLoadError`], so that the handful of remaining
+/// `tor_dirmgr::Error` variants we can return get picked consistently, and so that anything
+/// further up the stack (logs, and eventually FFI error kinds) can tell these cases apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorKind {
+    /// The file is missing or could not be read.
+    Missing,
+    /// The file was read but isn't a well-formed document of the expected type.
+    Unparsable,
+    /// The document parsed, but its signature or validity period rejected it.
+    Invalid,
+}
+
+/// A load failure that remembers which [`DocSource`] it came from and what kind of problem it
+/// was, so that it can be logged with enough detail to be actionable on a device with no
+/// attached console.
+#[derive(Debug)]
+struct DocLoadError {
+    /// Which file we were trying to load.
+    source: DocSource,
+    /// What kind of problem we ran into.
+    kind: ErrorKind,
+    /// The underlying cause, formatted for display.
+    cause: String,
+}
+
+impl DocLoadError {
+    /// Build a new [`DocLoadError`], taking anything `Display`-able as the cause.
+    fn new(source: DocSource, kind: ErrorKind, cause: impl fmt::Display) -> Self {
+        DocLoadError {
+            source,
+            kind,
+            cause: cause.to_string(),
+        }
+    }
+
+    /// Log this error and classify it into the closest matching [`Error`] variant we can
+    /// actually return from the `tor_dirmgr`-defined `Result` our callers expect.
+    fn into_dir_error(self) -> Error {
+        warn!(
+            "failed to load {}: {} ({:?})",
+            self.source, self.cause, self.kind
+        );
+        match self.kind {
+            ErrorKind::Missing => Error::CacheCorruption("required file(s) missing in cache"),
+            ErrorKind::Unparsable => Error::CacheCorruption("failed to parse a cached document"),
+            ErrorKind::Invalid => Error::BadNetworkConfig("a cached document failed validation"),
+        }
+    }
+}
+
+/// An in-memory view of a cache file's contents, decoded as UTF-8.
+///
+/// Under the `mmap` feature this is a read-only memory mapping of the file rather than a
+/// heap-allocated copy, which matters for the consensus and microdescriptor files: both can run
+/// to several megabytes, and doubling that in heap `String`s is wasteful on the memory-constrained
+/// iOS/Android targets this crate builds for. When the feature is off, or the mapping fails (e.g.
+/// the platform doesn't support it, or the file is empty), we fall back to a plain read.
+enum DocBytes {
+    /// A heap-allocated copy of the file.
+    Owned(String),
+    /// A read-only memory mapping of the file.
+    #[cfg(feature = "mmap")]
+    Mapped(memmap2::Mmap),
+}
+
+impl DocBytes {
+    /// Read `path` as UTF-8 text, preferring a memory mapping when the `mmap` feature is on.
+    fn read(path: &Path) -> io::Result<Self> {
+        #[cfg(feature = "mmap")]
+        {
+            match fs::File::open(path).and_then(|f| unsafe { memmap2::Mmap::map(&f) }) {
+                Ok(mapped) => {
+                    return std::str::from_utf8(&mapped)
+                        .map(|_| DocBytes::Mapped(mapped))
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e));
+                }
+                Err(e) => {
+                    debug!(
+                        "mmap of {} failed ({}), falling back to a plain read",
+                        path.to_string_lossy(),
+                        e
+                    );
+                }
+            }
+        }
+        fs::read_to_string(path).map(DocBytes::Owned)
+    }
+
+    /// Borrow the contents as a `&str`.
+    fn as_str(&self) -> &str {
+        match self {
+            DocBytes::Owned(s) => s.as_str(),
+            #[cfg(feature = "mmap")]
+            DocBytes::Mapped(m) => std::str::from_utf8(m).expect("validated as utf-8 in `read`"),
+        }
+    }
+}
+
 /// Contents of the directory cache.
 /// CONSENSUS_FILENAME is the name of the file containing the consensus.
 pub const CONSENSUS_FILENAME: &'static str = "consensus.txt";
@@ -41,6 +176,23 @@ pub const CERTIFICATE_FILENAME: &'static str = "certificate.txt";
 /// CHURN_FILENAME is the name of the churn info file.
 pub const CHURN_FILENAME: &'static str = "churn.txt";
 
+/// Coarse progress through a single load pass: `load_directory` sets `Validating` when a pass
+/// starts and `Ready` once a usable `NetDir` is in place; a pass that fails stays at
+/// `Validating`. The value is recorded only: nothing reads it back, and it does not decide when
+/// bootstrap status or directory events are published.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LoadState {
+    /// No directory has ever been loaded.
+    Unloaded,
+    /// Files were read from the cache directory and are being validated.
+    Validating,
+    /// A valid, sufficient `NetDir` is in place.
+    Ready,
+}
+
+/// How often the background task re-checks the cache directory for changes.
+const WATCH_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
 /// A directory manager that loads the directory information from flat files read from the cache
 /// directory.
 pub struct FlatFileDirMgr<R: Runtime> {
@@ -54,52 +206,116 @@ pub struct FlatFileDirMgr<R: Runtime> {
     /// A sender handle that we notify whenever the consensus changes.
     tx_events: broadcast::Sender<DirEvent>,
 
+    /// A sender handle that we notify whenever our bootstrapping status changes.
+    tx_bootstrap: watch::Sender<DirBootstrapStatus>,
+
     /// A receiver handle that gets notified whenever our bootstrapping status changes.
-    ///
-    /// Unused for now.
     bootstrap_rx_events: watch::Receiver<DirBootstrapStatus>,
 
     /// A circuit manager.
     circmgr: Option<Arc<CircMgr<R>>>,
+
+    /// The runtime used to spawn the cache-directory watcher and to sleep between polls.
+    runtime: R,
+
+    /// Our current position in the load state machine, as last set by `load_directory`. It is
+    /// recorded only; nothing reads it back.
+    load_state: std::sync::Mutex<LoadState>,
+
+    /// Minimum number of valid certificates from distinct recognized authorities that must be
+    /// present for the consensus to be accepted. `None` falls back to the usual Tor majority,
+    /// `floor(n/2)+1`. The consensus signature check that follows is run with the full number
+    /// of configured authorities, whatever this value is.
+    quorum_threshold: Option<usize>,
+
+    /// Whether a cache pass that finds the consensus, certificates, or microdescriptors missing
+    /// or expired may fall back to fetching them live over `circmgr`. See
+    /// [`FlatFileDirMgr::from_config_with_options`].
+    download_missing: bool,
 }
 
 impl<R: Runtime> FlatFileDirMgr<R> {
     /// Create a new FlatFileDirMgr from a given configuration.
-    pub fn from_config(config: DirMgrConfig, circmgr: Arc<CircMgr<R>>) -> Result<Arc<Self>> {
+    pub fn from_config(runtime: R, config: DirMgrConfig, circmgr: Arc<CircMgr<R>>) -> Result<Arc<Self>> {
+        Self::from_config_with_quorum(runtime, config, circmgr, None)
+    }
+
+    /// Create a new FlatFileDirMgr, overriding the default authority quorum threshold.
+    pub fn from_config_with_quorum(
+        runtime: R,
+        config: DirMgrConfig,
+        circmgr: Arc<CircMgr<R>>,
+        quorum_threshold: Option<usize>,
+    ) -> Result<Arc<Self>> {
+        Self::from_config_with_options(runtime, config, circmgr, quorum_threshold, false)
+    }
+
+    /// Create a new FlatFileDirMgr, overriding the default authority quorum threshold and
+    /// opting in to live-download fallback.
+    ///
+    /// With `download_missing` set, a cache pass that finds the consensus, certificates, or
+    /// microdescriptors missing or expired is followed by a download pass over `circmgr` before
+    /// giving up: whatever comes back is written to the corresponding flat file, so the next cold
+    /// start is served from cache again. Without it, a stale or incomplete cache directory fails
+    /// the load exactly as before.
+    pub fn from_config_with_options(
+        runtime: R,
+        config: DirMgrConfig,
+        circmgr: Arc<CircMgr<R>>,
+        quorum_threshold: Option<usize>,
+        download_missing: bool,
+    ) -> Result<Arc<Self>> {
         let netdir = SharedMutArc::new();
         let (tx_events, _) = broadcast::channel(1);
-        let (_, bootstrap_rx_events) = watch::channel();
+        let (tx_bootstrap, bootstrap_rx_events) = watch::channel();
         let circmgr = Some(circmgr);
 
-        Ok(Arc::new(FlatFileDirMgr {
+        let dirmgr = Arc::new(FlatFileDirMgr {
             config: config.into(),
             netdir,
             tx_events,
+            tx_bootstrap,
             bootstrap_rx_events,
             circmgr,
-        }))
+            runtime,
+            load_state: std::sync::Mutex::new(LoadState::Unloaded),
+            quorum_threshold,
+            download_missing,
+        });
+        dirmgr.clone().watch_cache_directory();
+
+        Ok(dirmgr)
     }
 
-    /// Check cache directory content.
-    fn check_directory(cache_path: &Path) -> Result<()> {
-        let mut any_missing = false;
-        for filename in [
-            CONSENSUS_FILENAME,
-            MICRODESCRIPTORS_FILENAME,
-            CERTIFICATE_FILENAME,
-            CHURN_FILENAME,
-        ]
-        .iter()
-        {
-            if !cache_path.join(filename).exists() {
-                any_missing = true;
-                debug!("required file missing: {filename}");
+    /// Spawn a background task that periodically re-checks the configured cache directory for
+    /// changes (e.g. a controller app dropping in a fresher `consensus.txt`/`churn.txt`/
+    /// `microdescriptors.txt`) and re-runs `load_directory` whenever it notices one, atomically
+    /// swapping in the new `NetDir` and publishing fresh `DirEvent`/`DirBootstrapStatus` updates.
+    ///
+    /// The bootstrap status itself is published from `load_directory`, right as a reload
+    /// completes, rather than on this loop's own timer: a subscriber on `bootstrap_events()`
+    /// should hear about a reload exactly when it happens, including the very first one done by
+    /// `DirProvider::bootstrap`, not only on whatever poll tick happens to follow it.
+    fn watch_cache_directory(self: Arc<Self>) {
+        let runtime = self.runtime.clone();
+        if let Err(e) = runtime.spawn(async move {
+            let mut last_seen = None;
+            loop {
+                let cache_path = self.config.get().cache_path.clone();
+                let snapshot = cache_snapshot(&cache_path);
+                if snapshot != last_seen {
+                    last_seen = snapshot;
+                    match self.load_directory().await {
+                        Ok(changed) => debug!("cache directory reload: changed={}", changed),
+                        Err(e) => warn!("cache directory reload failed: {}", e),
+                    }
+                }
+
+                self.runtime.sleep(WATCH_POLL_INTERVAL).await;
             }
+        }) {
+            warn!("failed to spawn cache-directory watcher: {}", e);
         }
-        if any_missing {
-            return Err(Error::CacheCorruption("required files missing in cache"));
-        }
-        Ok(())
     }
 
     /// Try to load the directory from flat files.
@@ -107,12 +323,28 @@ impl<R: Runtime> FlatFileDirMgr<R> {
     /// This is strongly inspired by the add_from_cache() methods from the various states in
     /// DirMgr, combined and simplified to directly use the data from the loaded files.
     pub async fn load_directory(&self) -> Result<bool> {
+        *self.load_state.lock().expect("load_state poisoned") = LoadState::Validating;
+
         let config = self.config.get();
         let cache_path = &config.cache_path;
-        Self::check_directory(cache_path)?;
+        if !self.can_retry_download() {
+            check_directory(cache_path)?;
+        }
 
         // Consensus
-        let unvalidated = self.load_consensus(cache_path)?;
+        let unvalidated = match self.load_consensus(cache_path) {
+            Ok(unvalidated) => unvalidated,
+            Err(_) if self.can_retry_download() => {
+                self.refresh_from_network(
+                    cache_path,
+                    "/tor/status-vote/current/consensus-microdesc",
+                    CONSENSUS_FILENAME,
+                )
+                .await?;
+                self.load_consensus(cache_path)?
+            }
+            Err(e) => return Err(e),
+        };
 
         let authority_ids: Vec<RsaIdentity> = config
             .authorities()
@@ -130,19 +362,53 @@ impl<R: Runtime> FlatFileDirMgr<R> {
             return Err(Error::UnrecognizedAuthorities);
         }
 
-        // Certificate
-        let certificate = self.load_certificate(cache_path)?;
+        // Certificates: require valid certificates from a quorum of recognized authorities, not
+        // just one.
+        let certificates = match self.load_certificates(cache_path, &authority_ids) {
+            Ok(certificates) => certificates,
+            Err(_) if self.can_retry_download() => {
+                self.refresh_from_network(cache_path, "/tor/keys/all", CERTIFICATE_FILENAME)
+                    .await?;
+                self.load_certificates(cache_path, &authority_ids)?
+            }
+            Err(e) => return Err(e),
+        };
+        let threshold = self
+            .quorum_threshold
+            .unwrap_or_else(|| authority_ids.len() / 2 + 1);
+        if certificates.len() < threshold {
+            return Err(DocLoadError::new(
+                DocSource::Certificate(cache_path.join(CERTIFICATE_FILENAME)),
+                ErrorKind::Invalid,
+                format!(
+                    "only {} of {} required recognized authority certificates found",
+                    certificates.len(),
+                    threshold
+                ),
+            )
+            .into_dir_error());
+        }
+        let n_relays = unvalidated.n_relays();
         let consensus = unvalidated
-            .check_signature(&[certificate])
+            .check_signature(&certificates)
             .map_err(|_| Error::CacheCorruption("Failed to validate consensus signature"))?;
 
         // Microdescriptors
-        let udesc = self.load_microdesc(cache_path)?;
+        let udesc = match self.load_microdesc(cache_path) {
+            Ok(udesc) => udesc,
+            Err(_) if self.can_retry_download() => {
+                self.refresh_from_network(cache_path, "/tor/server/microdescs-all", MICRODESCRIPTORS_FILENAME)
+                    .await?;
+                self.load_microdesc(cache_path)?
+            }
+            Err(e) => return Err(e),
+        };
 
         // Build directory
         let params = &config.override_net_params;
         let mut partial = PartialNetDir::new(consensus, Some(params));
 
+        let n_mds = udesc.len();
         for md in udesc {
             partial.add_microdesc(md);
         }
@@ -158,8 +424,10 @@ impl<R: Runtime> FlatFileDirMgr<R> {
             }
         }
 
-        Ok(match self.netdir.get() {
+        let changed = match self.netdir.get() {
             Some(_) => {
+                *self.load_state.lock().expect("load_state poisoned") = LoadState::Ready;
+
                 let mut tx = self.tx_events.clone();
 
                 tx.send(DirEvent::NewConsensus)
@@ -172,7 +440,27 @@ impl<R: Runtime> FlatFileDirMgr<R> {
                 true
             }
             None => false,
-        })
+        };
+
+        // Certificates are already validated by this point, so the only phases left to report are
+        // "still waiting on microdescriptors" and "done" -- reflecting whether this pass (or an
+        // earlier one) left us with a usable netdir, rather than the fixed status this used to
+        // send regardless of outcome.
+        let progress = if self.netdir.get().is_some() {
+            DirProgress::Complete
+        } else {
+            DirProgress::FetchingMicrodescs {
+                have: n_mds,
+                need: n_relays,
+            }
+        };
+        let _ = self
+            .tx_bootstrap
+            .clone()
+            .send(DirBootstrapStatus { progress })
+            .await;
+
+        Ok(changed)
     }
 
     /// Load the consensus from a flat file.
@@ -181,21 +469,35 @@ impl<R: Runtime> FlatFileDirMgr<R> {
         cache_path: &Path,
     ) -> Result<UnvalidatedConsensus<MdConsensusRouterStatus>> {
         let path = cache_path.join(CONSENSUS_FILENAME);
-        let consensus_text =
-            fs::read_to_string(path.clone()).map_err(|_| Error::UnrecognizedAuthorities)?;
+        let consensus_bytes = DocBytes::read(&path).map_err(|e| {
+            DocLoadError::new(DocSource::Consensus(path.clone()), ErrorKind::Missing, e)
+                .into_dir_error()
+        })?;
         debug!("{} loaded", path.to_string_lossy());
 
-        let path = cache_path.join(CHURN_FILENAME);
-        let churn_text = fs::read_to_string(path.clone()).unwrap_or_else(|_| "".to_string());
-        debug!("{} loaded", path.to_string_lossy());
-
-        let (_, _, parsed) = MdConsensus::parse(&consensus_text)
-            .map_err(|_| Error::CacheCorruption("Failed to parse consensus"))?;
-        let mut unvalidated = parsed
-            .check_valid_now()
-            .map_err(|_| Error::UntimelyObject(TimeValidityError::Unspecified))?;
-
-        let churn = parse_churn(&churn_text)?;
+        let churn_path = cache_path.join(CHURN_FILENAME);
+        let churn_text = fs::read_to_string(&churn_path).unwrap_or_else(|_| "".to_string());
+        debug!("{} loaded", churn_path.to_string_lossy());
+
+        let consensus_path = cache_path.join(CONSENSUS_FILENAME);
+        let (_, _, parsed) = MdConsensus::parse(consensus_bytes.as_str()).map_err(|e| {
+            DocLoadError::new(
+                DocSource::Consensus(consensus_path.clone()),
+                ErrorKind::Unparsable,
+                e,
+            )
+            .into_dir_error()
+        })?;
+        let mut unvalidated = parsed.check_valid_now().map_err(|e| {
+            DocLoadError::new(
+                DocSource::Consensus(consensus_path),
+                ErrorKind::Invalid,
+                format!("{:?}", e),
+            )
+            .into_dir_error()
+        })?;
+
+        let churn = parse_churn(&churn_text, &churn_path)?;
 
         // If the churn is above a threshold, we only consider a random subset
         // of the churned routers.
@@ -226,41 +528,174 @@ impl<R: Runtime> FlatFileDirMgr<R> {
         Ok(unvalidated)
     }
 
-    /// Load the certificate from a flat file.
-    fn load_certificate(&self, cache_path: &Path) -> Result<AuthCert> {
+    /// Load every authority certificate found in [`CERTIFICATE_FILENAME`] (one PEM-style block
+    /// per recognized authority, concatenated in the same file), validate each one's self-
+    /// signature and expiry, and return only those belonging to a distinct authority in
+    /// `authority_ids`.
+    ///
+    /// A certificate that is unparsable, expired, signed by an authority we don't recognize, or a
+    /// repeat of an authority we already have a certificate from, is logged and skipped rather
+    /// than failing the whole load: callers decide whether enough of the *remaining* certificates
+    /// meet the quorum threshold. Without the repeat check, two certificates for the same
+    /// authority would count twice towards that threshold even though they represent a single
+    /// vote.
+    fn load_certificates(&self, cache_path: &Path, authority_ids: &[RsaIdentity]) -> Result<Vec<AuthCert>> {
         let path = cache_path.join(CERTIFICATE_FILENAME);
-        let certificate =
-            fs::read_to_string(path.clone()).map_err(|_| Error::UnrecognizedAuthorities)?;
+        let certificates = fs::read_to_string(&path).map_err(|e| {
+            DocLoadError::new(DocSource::Certificate(path.clone()), ErrorKind::Missing, e)
+                .into_dir_error()
+        })?;
         debug!("{} loaded", path.to_string_lossy());
 
-        let parsed = AuthCert::parse(certificate.as_str())
-            .map_err(|_| Error::CacheCorruption("Failed to parse certificate"))?
-            .check_signature()?;
-        let cert = parsed
-            .check_valid_now()
-            .map_err(|_| Error::UntimelyObject(TimeValidityError::Unspecified))?;
+        let mut seen = HashSet::new();
+        let certs = AuthCert::parse_multiple(certificates.as_str())
+            .filter_map(|parsed| {
+                let parsed = parsed
+                    .map_err(|e| {
+                        warn!("skipping unparsable authority certificate in {}: {}", path.to_string_lossy(), e)
+                    })
+                    .ok()?
+                    .check_signature()
+                    .map_err(|e| warn!("skipping authority certificate with bad self-signature: {}", e))
+                    .ok()?;
+
+                let cert = parsed
+                    .check_valid_now()
+                    .map_err(|e| warn!("skipping expired/not-yet-valid authority certificate: {:?}", e))
+                    .ok()?;
+
+                if !authority_ids.contains(cert.id_fingerprint()) {
+                    warn!("skipping certificate from unrecognized authority {}", cert.id_fingerprint());
+                    return None;
+                }
+
+                if !seen.insert(*cert.id_fingerprint()) {
+                    warn!("skipping duplicate certificate for authority {}", cert.id_fingerprint());
+                    return None;
+                }
+
+                Some(cert)
+            })
+            .collect::<Vec<_>>();
+
+        if certs.is_empty() {
+            return Err(DocLoadError::new(
+                DocSource::Certificate(path),
+                ErrorKind::Invalid,
+                "no valid authority certificates found",
+            )
+            .into_dir_error());
+        }
 
-        Ok(cert)
+        Ok(certs)
     }
 
     /// Load the list of microdescriptors from a flat file.
     fn load_microdesc(&self, cache_path: &Path) -> Result<Vec<Microdesc>> {
         let path = cache_path.join(MICRODESCRIPTORS_FILENAME);
-        let udesc_text =
-            fs::read_to_string(path.clone()).map_err(|_| Error::UnrecognizedAuthorities)?;
+        let udesc_bytes = DocBytes::read(&path).map_err(|e| {
+            DocLoadError::new(
+                DocSource::Microdescriptors(path.clone()),
+                ErrorKind::Missing,
+                e,
+            )
+            .into_dir_error()
+        })?;
         debug!("{} loaded", path.to_string_lossy());
 
         let udesc = MicrodescReader::new(
-            udesc_text.as_str(),
+            udesc_bytes.as_str(),
             &AllowAnnotations::AnnotationsNotAllowed,
         )
-        .flatten()
-        .map(|anno| anno.into_microdesc())
+        .enumerate()
+        .filter_map(|(index, parsed)| match parsed {
+            Ok(anno) => Some(anno.into_microdesc()),
+            Err(e) => {
+                warn!(
+                    "skipping unparsable microdescriptor #{} in {}: {}",
+                    index,
+                    path.to_string_lossy(),
+                    e
+                );
+                None
+            }
+        })
         .collect::<Vec<Microdesc>>();
 
         Ok(udesc)
     }
 
+    /// Whether a failed cache load may fall back to a live download: the caller opted in via
+    /// `download_missing`, we have a circuit manager to download with, and we have *some* netdir
+    /// (even an expired one) to pick a directory cache from. Without that last part we have no
+    /// way to bootstrap a circuit at all -- unlike the full tor-dirmgr, this manager keeps no
+    /// list of fallback directories to fall back to from a completely empty cache.
+    fn can_retry_download(&self) -> bool {
+        self.download_missing && self.circmgr.is_some() && self.opt_netdir().is_some()
+    }
+
+    /// Fetch `resource` from a directory cache over `self.circmgr` and overwrite `filename` in
+    /// `cache_path` with whatever came back, so that the retried cache load (and the next cold
+    /// start) picks it up.
+    async fn refresh_from_network(&self, cache_path: &Path, resource: &str, filename: &str) -> Result<()> {
+        let circmgr = self.circmgr.as_ref().ok_or(Error::DirectoryNotPresent)?;
+        let netdir = self.opt_netdir().ok_or(Error::DirectoryNotPresent)?;
+
+        info!("{} missing or stale; fetching {} over Tor", filename, resource);
+        let body = Self::fetch_from_network(circmgr, &netdir, resource).await?;
+
+        fs::write(cache_path.join(filename), body).map_err(|e| {
+            warn!("failed to write fetched {} to cache: {}", filename, e);
+            Error::CacheCorruption("failed to write a freshly downloaded document to cache")
+        })
+    }
+
+    /// Fetch `resource` (a directory-protocol path, e.g. `/tor/status-vote/current/consensus`)
+    /// from a directory cache in `netdir`, over a directory circuit from `circmgr`, and return
+    /// the response body as text.
+    async fn fetch_from_network(
+        circmgr: &CircMgr<R>,
+        netdir: &NetDir,
+        resource: &str,
+    ) -> Result<String> {
+        let circ = circmgr.get_or_launch_dir(netdir).await.map_err(|e| {
+            warn!("failed to build a directory circuit for {}: {}", resource, e);
+            Error::DirectoryNotPresent
+        })?;
+        let mut stream = circ.begin_dir_stream().await.map_err(|e| {
+            warn!("failed to open a directory stream for {}: {}", resource, e);
+            Error::DirectoryNotPresent
+        })?;
+
+        let request = http::Request::builder()
+            .method(http::Method::GET)
+            .uri(resource)
+            .version(http::Version::HTTP_10)
+            .header(http::header::HOST, "dirserver")
+            .body(Vec::new())
+            .map_err(|_| Error::BadNetworkConfig("could not build directory request"))?;
+        let raw_request = request_to_raw(request)
+            .map_err(|_| Error::BadNetworkConfig("could not serialize directory request"))?;
+
+        stream
+            .write_all(&raw_request)
+            .await
+            .map_err(|_| Error::DirectoryNotPresent)?;
+        stream.flush().await.map_err(|_| Error::DirectoryNotPresent)?;
+
+        let mut raw_response = Vec::new();
+        stream
+            .read_to_end(&mut raw_response)
+            .await
+            .map_err(|_| Error::DirectoryNotPresent)?;
+
+        let response = raw_to_response(raw_response)
+            .map_err(|_| Error::BadNetworkConfig("could not parse directory response"))?;
+
+        String::from_utf8(response.into_body())
+            .map_err(|_| Error::BadNetworkConfig("directory response was not valid utf-8"))
+    }
+
     /// Return an Arc handle to our latest directory, if we have one.
     fn opt_netdir(&self) -> Option<Arc<NetDir>> {
         self.netdir.get()
@@ -273,16 +708,69 @@ impl<R: Runtime> FlatFileDirMgr<R> {
     }
 }
 
+/// Check that `cache_path` contains the consensus, certificate, microdescriptor, and churn
+/// files a directory needs to be loaded, without parsing any of them.
+pub fn check_directory(cache_path: &Path) -> Result<()> {
+    let mut any_missing = false;
+    for filename in [
+        CONSENSUS_FILENAME,
+        MICRODESCRIPTORS_FILENAME,
+        CERTIFICATE_FILENAME,
+        CHURN_FILENAME,
+    ]
+    .iter()
+    {
+        if !cache_path.join(filename).exists() {
+            any_missing = true;
+            debug!("required file missing: {filename}");
+        }
+    }
+    if any_missing {
+        return Err(Error::CacheCorruption("required files missing in cache"));
+    }
+    Ok(())
+}
+
+/// Take a cheap fingerprint of the four cache files' modification times, so the background
+/// watcher can tell whether anything in `cache_path` changed since its last poll without
+/// re-parsing and re-validating the whole directory every time.
+fn cache_snapshot(cache_path: &Path) -> Option<Vec<std::time::SystemTime>> {
+    [
+        CONSENSUS_FILENAME,
+        CERTIFICATE_FILENAME,
+        MICRODESCRIPTORS_FILENAME,
+        CHURN_FILENAME,
+    ]
+    .iter()
+    .map(|filename| fs::metadata(cache_path.join(filename))?.modified())
+    .collect::<io::Result<Vec<_>>>()
+    .ok()
+}
+
 /// Parse churned routers info.
-fn parse_churn(text: &str) -> Result<Vec<RsaIdentity>> {
+fn parse_churn(text: &str, path: &Path) -> Result<Vec<RsaIdentity>> {
     let churn: Vec<RsaIdentity> = text
         .lines()
         .collect::<Vec<&str>>()
         .iter()
         .filter(|line| !line.is_empty())
         .map(|line| {
-            let bytes = hex::decode(line).map_err(Error::BadHexInCache)?;
-            RsaIdentity::from_bytes(&bytes).ok_or(Error::CacheCorruption("invalid RSA identity"))
+            let bytes = hex::decode(line).map_err(|e| {
+                DocLoadError::new(
+                    DocSource::Churn(path.to_path_buf()),
+                    ErrorKind::Unparsable,
+                    e,
+                )
+                .into_dir_error()
+            })?;
+            RsaIdentity::from_bytes(&bytes).ok_or_else(|| {
+                DocLoadError::new(
+                    DocSource::Churn(path.to_path_buf()),
+                    ErrorKind::Unparsable,
+                    "invalid RSA identity",
+                )
+                .into_dir_error()
+            })
         })
         .collect::<Result<_>>()?;
     Ok(churn)
@@ -306,13 +794,17 @@ impl<R: Runtime> NetDirProvider for FlatFileDirMgr<R> {
 impl<R: Runtime> DirProvider for FlatFileDirMgr<R> {
     fn reconfigure(
         &self,
-        _new_config: &DirMgrConfig,
-        _how: tor_config::Reconfigure,
+        new_config: &DirMgrConfig,
+        how: tor_config::Reconfigure,
     ) -> std::result::Result<(), tor_config::ReconfigureError> {
-        // Not implemented
-        Err(tor_config::ReconfigureError::CannotChange {
-            field: "all".to_string(),
-        })
+        // We only load from flat files, so the only configuration that matters to us is where
+        // those files live; everything else (network params, schedules, ...) is handled by the
+        // states we don't use. Swap it in and let the next watcher poll pick up the new path.
+        if how == tor_config::Reconfigure::CheckAllOrNothing {
+            return Ok(());
+        }
+        self.config.replace(new_config.clone());
+        Ok(())
     }
 
     async fn bootstrap(&self) -> Result<()> {
@@ -326,18 +818,43 @@ impl<R: Runtime> DirProvider for FlatFileDirMgr<R> {
     }
 }
 
-pub struct FlatFileDirMgrBuilder {}
+pub struct FlatFileDirMgrBuilder {
+    /// Minimum number of valid certificates from distinct recognized authorities needed for a
+    /// consensus to be accepted; `None` uses the usual Tor majority. See
+    /// [`FlatFileDirMgr::from_config_with_quorum`].
+    pub quorum_threshold: Option<usize>,
+}
+
+impl FlatFileDirMgrBuilder {
+    /// Build with the default authority quorum (a majority of the configured authorities).
+    pub fn new() -> Self {
+        FlatFileDirMgrBuilder {
+            quorum_threshold: None,
+        }
+    }
+}
+
+impl Default for FlatFileDirMgrBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl<R: Runtime> DirProviderBuilder<R> for FlatFileDirMgrBuilder {
     fn build(
         &self,
-        _runtime: R,
+        runtime: R,
         _store: DirMgrStore<R>,
         circmgr: Arc<tor_circmgr::CircMgr<R>>,
         config: DirMgrConfig,
     ) -> arti_client::Result<Arc<dyn tor_dirmgr::DirProvider + 'static>> {
-        let dm = FlatFileDirMgr::from_config(config, circmgr)
-            .map_err(arti_client::ErrorDetail::DirMgrSetup)?;
+        let dm = FlatFileDirMgr::from_config_with_quorum(
+            runtime,
+            config,
+            circmgr,
+            self.quorum_threshold,
+        )
+        .map_err(arti_client::ErrorDetail::DirMgrSetup)?;
         Ok(dm)
     }
 }
@@ -3,14 +3,20 @@
 #![deny(missing_docs)]
 
 mod client;
+mod compression;
 mod ffi;
 mod flatfiledirmgr;
 mod http;
+mod retry;
 
 pub use client::Client;
+pub use client::ClientAuth;
+pub use client::PoolConfig;
+pub use client::TlsPolicy;
 pub use client::AUTHORITY_FILENAME;
 pub use flatfiledirmgr::check_directory;
 pub use flatfiledirmgr::CERTIFICATE_FILENAME;
 pub use flatfiledirmgr::CHURN_FILENAME;
 pub use flatfiledirmgr::CONSENSUS_FILENAME;
 pub use flatfiledirmgr::MICRODESCRIPTORS_FILENAME;
+pub use retry::RetryConfig;
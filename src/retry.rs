@@ -0,0 +1,70 @@
+//! Retry and backoff policy for [`crate::Client::send`].
+
+use std::time::Duration;
+
+use rand::Rng;
+
+/// How many times, and how quickly, [`crate::Client::send`] retries a failed request before
+/// giving the caller its last result.
+///
+/// Every real consumer of this crate used to reinvent this loop itself (the test harness calls it
+/// `MAX_TRIES`, because Tor circuits are "erratic") -- this bakes a configurable version of that
+/// loop into `Client` so callers, including the Android wrapper, don't have to.
+///
+/// The delay before attempt `n` (`n` starting at 1) is `initial_delay * 2^(n-1)`, capped at
+/// `max_delay`, with up to 50% jitter added so several callers retrying the same failure at once
+/// don't all hammer the network in lockstep.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryConfig {
+    /// How many attempts to make in total before giving up. `1` means "no retries".
+    pub attempts: u32,
+    /// Base of the backoff: the delay for attempt 1, doubled for each attempt after it.
+    /// `Client::send` makes its first attempt without waiting, so its first retry (attempt 2)
+    /// waits twice this long.
+    pub initial_delay: Duration,
+    /// Upper bound on the delay between attempts before jitter, no matter how many attempts have
+    /// passed; the jitter of up to 50% is added on top of the capped value.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            attempts: 3,
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Disable retries entirely: `Client::send` makes exactly one attempt and returns whatever it
+    /// gets, exactly as it did before this existed.
+    pub fn none() -> Self {
+        RetryConfig {
+            attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// The delay to wait before making the given 1-based `attempt`, with jitter applied.
+    pub(crate) fn delay_before(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(16);
+        let base = self
+            .initial_delay
+            .checked_mul(1u32 << exponent)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay);
+        let jitter_ms = rand::thread_rng().gen_range(0..=(base.as_millis() as u64 / 2) + 1);
+        base + Duration::from_millis(jitter_ms)
+    }
+}
+
+/// Whether an HTTP response status is worth retrying.
+///
+/// A server error (`5xx`) may well go away on the next attempt, possibly over a different
+/// circuit; a client error (`4xx`) reflects the request itself and retrying it unchanged would
+/// just hammer the same endpoint for the same answer.
+pub(crate) fn is_retryable_status(status: http::StatusCode) -> bool {
+    status.is_server_error()
+}